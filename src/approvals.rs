@@ -0,0 +1,64 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use ethers::prelude::*;
+use rustc_hash::FxHashMap;
+
+use crate::pair::Pair;
+use crate::v2protocol::{get_all_pairs, Protocol, WSClient};
+use crate::{erc20, ArbContract};
+
+/// Allowances below this are treated as "missing" and re-approved; well
+/// above any notional we would ever risk in a single arbitrage.
+const MIN_ALLOWANCE: U256 = U256([u64::MAX, u64::MAX, 0, 0]);
+
+/// Checks the ArbContract's ERC-20 allowance toward every pool it could be
+/// asked to swap through, and submits `approveToken` for any that are
+/// missing, instead of discovering mid-arb that a path reverts on
+/// `transferFrom`.
+pub async fn ensure_router_approvals(
+    client: WSClient,
+    arb_contract: &ArbContract<WSClient>,
+    owner: &LocalWallet,
+    protocols: &HashMap<Address, Protocol>,
+    custom_pairs: &FxHashMap<(Address, Address), Pair>,
+) -> Result<()> {
+    let mut required: HashSet<(Address, Address)> = HashSet::new();
+    for pair in get_all_pairs(protocols.values()).chain(custom_pairs.values()) {
+        let (token0, token1) = pair.get_tokens();
+        let spender = pair.contract.address();
+        required.insert((token0, spender));
+        required.insert((token1, spender));
+    }
+
+    for (token, spender) in required {
+        let token_contract: erc20<WSClient> = erc20::new(token, client.clone());
+        let allowance = token_contract
+            .allowance(arb_contract.address(), spender)
+            .call()
+            .await?;
+
+        if allowance < MIN_ALLOWANCE {
+            println!("Approving {:#x} for pool {:#x}", token, spender);
+            approve(client.clone(), arb_contract, owner, token, spender).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn approve(
+    client: WSClient,
+    arb_contract: &ArbContract<WSClient>,
+    owner: &LocalWallet,
+    token: Address,
+    spender: Address,
+) -> Result<()> {
+    let mut call = arb_contract.approve_token(token, spender, U256::max_value());
+    let nonce = client.get_transaction_count(owner.address(), None).await?;
+    call.tx.set_nonce(nonce);
+    let signature = owner.sign_transaction(&call.tx).await?;
+    let tx = call.tx.rlp_signed(&signature);
+    client.send_raw_transaction(tx).await?.await?;
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use ethers::types::U256;
+
+/// Determines how much of the contract's available balance we are willing to
+/// risk on a single arbitrage attempt, instead of always handing the
+/// optimizer the full balance. Larger inputs invite worse slippage and
+/// bigger revert losses when our model of the pools is wrong.
+#[derive(Debug, Clone, Copy)]
+pub enum BankrollPolicy {
+    /// Offer the optimizer the full available balance (previous behaviour).
+    Full,
+    /// Offer at most this fraction of the balance, in basis points.
+    Fraction(u32),
+    /// Offer at most this absolute notional, whichever is lower.
+    FixedMax(U256),
+}
+
+impl BankrollPolicy {
+    const FRACTION_BASE: u32 = 10000;
+
+    pub fn cap(&self, balance: U256) -> U256 {
+        match self {
+            Self::Full => balance,
+            Self::Fraction(bps) => balance
+                .saturating_mul((*bps).into())
+                .checked_div(Self::FRACTION_BASE.into())
+                .unwrap_or_default(),
+            Self::FixedMax(max) => balance.min(*max),
+        }
+    }
+}
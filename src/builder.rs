@@ -0,0 +1,50 @@
+use anyhow::{anyhow, Result};
+use ethers::prelude::*;
+
+use crate::v2protocol::WSClient;
+use crate::ArbContract;
+
+/// Fraction (basis points) of realized profit shared with the block builder
+/// on chains where coinbase tips, rather than gas price, win priority.
+/// `None` disables builder payments entirely (previous behaviour).
+pub fn compute_bribe(profit: U256, fraction_bps: Option<u32>) -> U256 {
+    match fraction_bps {
+        Some(bps) if bps > 0 => profit.saturating_mul(bps.into()) / 10000,
+        _ => U256::zero(),
+    }
+}
+
+/// Sends `amount` from the ArbContract to `mined_block`'s coinbase - the
+/// block that actually mined the arbitrage, not whatever's latest by the
+/// time this runs. By this point in the call chain several awaits have
+/// already passed since the trade mined, so "latest" can easily be a later
+/// block than the one that mined it, and tipping that block's author pays
+/// the wrong builder.
+pub async fn tip_builder(
+    client: WSClient,
+    arb_contract: &ArbContract<WSClient>,
+    owner: &LocalWallet,
+    amount: U256,
+    mined_block: H256,
+) -> Result<()> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+
+    let builder = client
+        .get_block(mined_block)
+        .await?
+        .and_then(|block| block.author)
+        .ok_or_else(|| anyhow!("Mined block has no author"))?;
+
+    let mut call = arb_contract.tip_builder(builder, amount);
+    let nonce = client.get_transaction_count(owner.address(), None).await?;
+    call.tx.set_nonce(nonce);
+    let signature = owner.sign_transaction(&call.tx).await?;
+    let tx = call.tx.rlp_signed(&signature);
+    client.send_raw_transaction(tx).await?.await?;
+
+    println!("Tipped builder {:#x} with {}", builder, amount);
+
+    Ok(())
+}
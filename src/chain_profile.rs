@@ -0,0 +1,98 @@
+use ethers::types::U256;
+
+/// Per-chain rules consulted by the gas-pricing logic in
+/// `wallet_strategy::scale_gas` and `estimate_gas`, since the forks this bot
+/// targets disagree on how aggressively a resubmission needs to bid and on
+/// which transaction types they'll even accept.
+#[derive(Debug, Clone, Copy)]
+pub struct ChainProfile {
+    /// Minimum basis points a replacement bid must clear over a stuck
+    /// transaction's own gas before a node will treat it as distinct rather
+    /// than a duplicate of an existing pending one. Not yet consulted
+    /// anywhere; reserved for a future resubmit-with-bumped-gas flow -
+    /// ordinary first-time submissions have no pending transaction to clear.
+    pub min_replacement_bump_bps: u32,
+    /// Whether this chain accepts EIP-1559 (type 2) transactions; chains
+    /// that don't are sent the legacy equivalent instead.
+    pub supports_eip1559: bool,
+    /// Largest transaction size this chain's nodes will accept, in bytes.
+    /// Not yet enforced anywhere; reserved for a future calldata-size guard
+    /// in the call-builder once paths get long enough for it to matter.
+    pub max_tx_size_bytes: usize,
+    /// Gas used by a single-hop arbitrage on this chain, consulted by
+    /// `estimate_gas`. `gas_per_hop` is added for each hop beyond the
+    /// first, since longer paths cost roughly linearly more gas.
+    pub base_gas_estimate: u32,
+    pub gas_per_hop: u32,
+    /// Divisor applied to the success-case gas estimate to approximate the
+    /// cheaper gas spent on a reverted attempt, e.g. `8` means a revert
+    /// costs roughly 1/8th of a successful trade. Chains where a revert
+    /// still pays most of the success-case cost (e.g. an L1 calldata-posting
+    /// fee charged regardless of outcome) should use a smaller divisor.
+    pub fail_gas_divisor: u32,
+}
+
+impl Default for ChainProfile {
+    /// Conservative defaults for a chain we don't have a specific profile
+    /// for: assume the largest required bump, no EIP-1559 support, and the
+    /// bot's long-standing flat gas estimate, since guessing too
+    /// aggressively in any direction risks either a rejected resubmission
+    /// or a badly mispriced trade.
+    fn default() -> Self {
+        Self {
+            min_replacement_bump_bps: 2000,
+            supports_eip1559: false,
+            max_tx_size_bytes: 32 * 1024,
+            base_gas_estimate: 400_000,
+            gas_per_hop: 100_000,
+            fail_gas_divisor: 8,
+        }
+    }
+}
+
+/// Looks up the replacement/laddering rules for `chain_id`, falling back to
+/// `ChainProfile::default` for chains we haven't profiled yet.
+pub fn for_chain_id(chain_id: U256) -> ChainProfile {
+    match chain_id.as_u64() {
+        // Ethereum mainnet
+        1 => ChainProfile {
+            min_replacement_bump_bps: 1000,
+            supports_eip1559: true,
+            max_tx_size_bytes: 128 * 1024,
+            base_gas_estimate: 180_000,
+            gas_per_hop: 120_000,
+            fail_gas_divisor: 8,
+        },
+        // Polygon PoS: congestion makes a 10% bump an unreliable floor.
+        137 => ChainProfile {
+            min_replacement_bump_bps: 1500,
+            supports_eip1559: true,
+            max_tx_size_bytes: 128 * 1024,
+            base_gas_estimate: 150_000,
+            gas_per_hop: 100_000,
+            fail_gas_divisor: 8,
+        },
+        // BNB Smart Chain: predates EIP-1559 support in this bot's target forks.
+        56 => ChainProfile {
+            min_replacement_bump_bps: 1000,
+            supports_eip1559: false,
+            max_tx_size_bytes: 128 * 1024,
+            base_gas_estimate: 150_000,
+            gas_per_hop: 90_000,
+            fail_gas_divisor: 8,
+        },
+        // Arbitrum One: gas units run far higher than an L1 swap since they
+        // fold in an L1 calldata-posting cost, and that cost is paid whether
+        // or not the call reverts, so a failed attempt is much closer in
+        // cost to a successful one than the other chains' /8 assumes.
+        42161 => ChainProfile {
+            min_replacement_bump_bps: 1000,
+            supports_eip1559: true,
+            max_tx_size_bytes: 128 * 1024,
+            base_gas_estimate: 900_000,
+            gas_per_hop: 350_000,
+            fail_gas_divisor: 3,
+        },
+        _ => ChainProfile::default(),
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use deadpool_sqlite::Pool;
+use ethers::prelude::*;
+
+use crate::snapshots::{self, ReconstructionMode};
+use crate::v2protocol::{resolve_abi, update_all_pairs, Protocol, RawProtocol, WSClient};
+use crate::PROTOCOLS_PATH;
+
+const DEFAULT_SWAP_FEE: u32 = 30;
+const FEE_GETTER_CANDIDATES: [&str; 4] = ["swapFee", "getFee", "fee", "totalFee"];
+const ROUTER_FUNCTION_CANDIDATES: [&str; 3] = ["WETH", "factory", "getAmountsOut"];
+
+/// Options for `rustarb add-protocol`, parsed from the command line.
+pub struct AddProtocolArgs {
+    pub factory: Address,
+    pub router: Address,
+    pub name: String,
+}
+
+/// Onboards a new Uniswap-V2-style fork: resolves the factory/router ABIs
+/// (fetching and caching them from the block explorer if we don't already
+/// have them), probes the factory for a swap fee getter and the router for
+/// the functions we rely on elsewhere, runs an initial pair sync to make
+/// sure the contracts actually behave like a V2 fork, and appends the
+/// resulting entry to `protocols.json`. Anything it couldn't infer is
+/// printed so it can be fixed by hand before the bot is restarted.
+pub async fn add_protocol(args: AddProtocolArgs, client: WSClient, pool: Arc<Pool>) -> Result<()> {
+    let factory_abi = resolve_abi(None, args.factory).await?;
+    let router_abi = resolve_abi(None, args.router).await?;
+
+    let factory_contract =
+        ethers::contract::Contract::new(args.factory, factory_abi, client.clone());
+    let router_contract =
+        ethers::contract::Contract::new(args.router, router_abi, client.clone());
+
+    let swap_fee = match probe_swap_fee(&factory_contract).await {
+        Some(fee) => {
+            println!("Inferred swap fee: {} bps", fee);
+            fee
+        }
+        None => {
+            println!(
+                "Could not infer swap fee from the factory, defaulting to {} bps; edit protocols.json if this is wrong",
+                DEFAULT_SWAP_FEE
+            );
+            DEFAULT_SWAP_FEE
+        }
+    };
+
+    for name in ROUTER_FUNCTION_CANDIDATES {
+        if !router_contract.abi().functions().any(|f| f.name == name) {
+            println!(
+                "Router does not expose `{}`; anything relying on it may not work for this protocol",
+                name
+            );
+        }
+    }
+
+    let raw = RawProtocol::new(args.factory, swap_fee, args.name.clone(), args.router);
+    let protocol = Protocol::new(raw, client.clone(), pool).await?;
+    let mut protocols = HashMap::with_capacity(1);
+    protocols.insert(protocol.factory.address(), protocol);
+
+    let protocols = update_all_pairs(protocols, client).await?;
+    let protocol = protocols
+        .into_values()
+        .next()
+        .expect("Just-inserted protocol missing after sync");
+    println!(
+        "Initial pair sync found {} pair(s) for {}",
+        protocol.pairs.len(),
+        args.name
+    );
+
+    append_protocol_entry(args.factory, swap_fee, &args.name, args.router).await?;
+    println!("Wrote {} entry to {}", args.name, PROTOCOLS_PATH.as_str());
+
+    Ok(())
+}
+
+async fn probe_swap_fee(factory: &ethers::contract::Contract<WSClient>) -> Option<u32> {
+    for name in FEE_GETTER_CANDIDATES {
+        let Ok(call) = factory.method::<_, U256>(name, ()) else {
+            continue;
+        };
+        if let Ok(value) = call.call().await {
+            if value <= U256::from(u32::MAX) {
+                return Some(value.as_u32());
+            }
+        }
+    }
+    None
+}
+
+async fn append_protocol_entry(
+    factory: Address,
+    swap_fee: u32,
+    name: &str,
+    router: Address,
+) -> Result<()> {
+    let raw_text = tokio::fs::read_to_string(PROTOCOLS_PATH.as_str()).await?;
+    let mut file: serde_json::Value = serde_json::from_str(raw_text.as_str())?;
+    let protocols = file
+        .get_mut("protocols")
+        .and_then(serde_json::Value::as_array_mut)
+        .ok_or_else(|| {
+            anyhow!(
+                "{} is missing a top-level \"protocols\" array",
+                PROTOCOLS_PATH.as_str()
+            )
+        })?;
+    protocols.push(serde_json::json!({
+        "name": name,
+        "factory_addr": format!("{:#x}", factory),
+        "swap_fee": swap_fee,
+        "router_address": format!("{:#x}", router),
+    }));
+    tokio::fs::write(
+        PROTOCOLS_PATH.as_str(),
+        serde_json::to_string_pretty(&file)?,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Options for `rustarb reconstruct-reserves`, parsed from the command line.
+pub struct ReconstructReservesArgs {
+    pub block: u64,
+    pub mode: ReconstructionMode,
+}
+
+/// Reconstructs every known pair's reserves as of a historical block and
+/// writes them into the `reserve_snapshots` table, so a backtest can later
+/// load a consistent cross-section of reserves for a period before the bot
+/// was recording live data. See `snapshots::reconstruct_reserves_at_block`
+/// for how the two reconstruction modes differ.
+pub async fn reconstruct_reserves(
+    args: ReconstructReservesArgs,
+    client: WSClient,
+    pool: Arc<Pool>,
+) -> Result<()> {
+    let (protocols, custom_pairs) =
+        crate::reload_protocols_and_pairs(client.clone(), pool.clone()).await?;
+    snapshots::ensure_schema(&pool).await?;
+    let reconstructed = snapshots::reconstruct_reserves_at_block(
+        client,
+        &protocols,
+        &custom_pairs,
+        pool,
+        args.block,
+        args.mode,
+    )
+    .await?;
+    println!(
+        "Reconstructed reserves for {} pair(s) as of block {}",
+        reconstructed, args.block
+    );
+    Ok(())
+}
+
+/// Parses `reconstruct-reserves --block <N> [--from-block <M>]` out of the
+/// raw process arguments. `--from-block` replays `Sync` events up to
+/// `--block` instead of issuing an archive `getReserves` call pinned to it;
+/// omit it when the RPC endpoint is an archive node.
+pub fn parse_reconstruct_reserves_args(args: &[String]) -> Result<ReconstructReservesArgs> {
+    let mut block = None;
+    let mut from_block = None;
+
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| anyhow!("Missing value for {}", flag))?;
+        match flag.as_str() {
+            "--block" => block = Some(value.parse::<u64>()?),
+            "--from-block" => from_block = Some(value.parse::<u64>()?),
+            other => return Err(anyhow!("Unknown flag {}", other)),
+        }
+    }
+
+    let block = block.ok_or_else(|| anyhow!("--block is required"))?;
+    let mode = match from_block {
+        Some(from_block) => ReconstructionMode::SyncReplay { from_block },
+        None => ReconstructionMode::ArchiveCall,
+    };
+
+    Ok(ReconstructReservesArgs { block, mode })
+}
+
+/// Parses `add-protocol --factory 0x.. --router 0x.. [--name Foo]` out of the
+/// raw process arguments (excluding the binary name and subcommand).
+pub fn parse_add_protocol_args(args: &[String]) -> Result<AddProtocolArgs> {
+    let mut factory = None;
+    let mut router = None;
+    let mut name = None;
+
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| anyhow!("Missing value for {}", flag))?;
+        match flag.as_str() {
+            "--factory" => factory = Some(value.parse::<Address>()?),
+            "--router" => router = Some(value.parse::<Address>()?),
+            "--name" => name = Some(value.clone()),
+            other => return Err(anyhow!("Unknown flag {}", other)),
+        }
+    }
+
+    let factory = factory.ok_or_else(|| anyhow!("--factory is required"))?;
+    let router = router.ok_or_else(|| anyhow!("--router is required"))?;
+    let name = name.unwrap_or_else(|| format!("{:#x}", factory));
+
+    Ok(AddProtocolArgs {
+        factory,
+        router,
+        name,
+    })
+}
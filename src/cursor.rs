@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use ethers::abi::{decode, ParamType};
+use ethers::prelude::*;
+use ethers::types::Filter;
+use ethers::utils::keccak256;
+use rustc_hash::FxHashMap;
+
+use crate::pair::Pair;
+use crate::v2protocol::{Protocol, WSClient};
+
+const SYNC_EVENT_SIGNATURE: &str = "Sync(uint112,uint112)";
+
+/// How far we've gotten processing new blocks and refreshing reserves,
+/// persisted across restarts so we can tell how stale our local state is
+/// on boot instead of silently trusting whatever was last written to the
+/// database.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct BlockCursor {
+    pub last_processed_block: Option<u64>,
+    pub last_reserve_sync_block: Option<u64>,
+}
+
+/// Loads the persisted cursor, treating a missing or unreadable file as "no
+/// prior run" rather than an error.
+pub async fn load_cursor(path: &str) -> BlockCursor {
+    match tokio::fs::read_to_string(path).await {
+        Ok(raw) => serde_json::from_str(raw.as_str()).unwrap_or_default(),
+        Err(_) => BlockCursor::default(),
+    }
+}
+
+pub async fn save_cursor(path: &str, cursor: BlockCursor) -> Result<()> {
+    tokio::fs::write(path, serde_json::to_string_pretty(&cursor)?).await?;
+    Ok(())
+}
+
+/// A point-in-time copy of every known pair's reserves, persisted so a
+/// restart has a real baseline to resume from instead of the zeroed reserves
+/// every pair starts a fresh process with (`pair_data.db` only caches pair
+/// addresses/tokens, never reserves). `block_number` is the block the
+/// reserves were read as of, so a restart can tell how stale this snapshot
+/// is and how far it needs to replay `Sync` logs to catch back up.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ReserveSnapshot {
+    pub block_number: u64,
+    reserves: HashMap<Address, (u128, u128)>,
+}
+
+/// Reads every known pair's current reserves into a `ReserveSnapshot`, so it
+/// can be persisted and replayed onto after a restart.
+pub fn collect_reserve_snapshot(
+    protocols: &HashMap<Address, Protocol>,
+    custom_pairs: &FxHashMap<(Address, Address), Pair>,
+    block_number: u64,
+) -> ReserveSnapshot {
+    let reserves = protocols
+        .values()
+        .flat_map(|protocol| protocol.pairs.values())
+        .chain(custom_pairs.values())
+        .map(|pair| (pair.contract.address(), (pair.reserve0, pair.reserve1)))
+        .collect();
+    ReserveSnapshot {
+        block_number,
+        reserves,
+    }
+}
+
+/// Applies a previously-collected snapshot's reserves onto the current
+/// `protocols`/`custom_pairs`, by pool address. Pairs the snapshot has no
+/// entry for (e.g. ones added since it was taken) are left at their current
+/// reserves rather than zeroed.
+pub fn apply_reserve_snapshot(
+    protocols: &mut HashMap<Address, Protocol>,
+    custom_pairs: &mut FxHashMap<(Address, Address), Pair>,
+    snapshot: &ReserveSnapshot,
+) {
+    for pair in protocols
+        .values_mut()
+        .flat_map(|protocol| protocol.pairs.values_mut())
+        .chain(custom_pairs.values_mut())
+    {
+        if let Some(&(reserve0, reserve1)) = snapshot.reserves.get(&pair.contract.address()) {
+            pair.reserve0 = reserve0;
+            pair.reserve1 = reserve1;
+        }
+    }
+}
+
+/// Loads the persisted reserve snapshot, treating a missing or unreadable
+/// file as "no snapshot available" rather than an error.
+pub async fn load_reserve_snapshot(path: &str) -> Option<ReserveSnapshot> {
+    let raw = tokio::fs::read_to_string(path).await.ok()?;
+    serde_json::from_str(raw.as_str()).ok()
+}
+
+pub async fn save_reserve_snapshot(path: &str, snapshot: &ReserveSnapshot) -> Result<()> {
+    tokio::fs::write(path, serde_json::to_string(snapshot)?).await?;
+    Ok(())
+}
+
+fn sync_topic() -> H256 {
+    H256::from(keccak256(SYNC_EVENT_SIGNATURE.as_bytes()))
+}
+
+/// Replays `Sync` events for every known pair between `from_block` and
+/// `to_block` (inclusive) and applies them directly to local reserves, to
+/// patch a `ReserveSnapshot` baseline back up to date. Much cheaper than a
+/// full multicall refresh across every pair when we're only a handful of
+/// blocks behind after a restart - but only correct when applied on top of a
+/// real baseline; a pair that traded outside the window keeps whatever the
+/// baseline already had for it, which is only right if the baseline itself
+/// was accurate as of `from_block`.
+pub async fn replay_reserves_from_logs(
+    client: WSClient,
+    protocols: &mut HashMap<Address, Protocol>,
+    custom_pairs: &mut FxHashMap<(Address, Address), Pair>,
+    from_block: u64,
+    to_block: u64,
+) -> Result<()> {
+    let mut pool_index: FxHashMap<Address, (Option<Address>, (Address, Address))> =
+        FxHashMap::default();
+    for (factory_address, protocol) in protocols.iter() {
+        for (key, pair) in &protocol.pairs {
+            pool_index.insert(pair.contract.address(), (Some(*factory_address), *key));
+        }
+    }
+    for (key, pair) in custom_pairs.iter() {
+        pool_index.insert(pair.contract.address(), (None, *key));
+    }
+
+    if pool_index.is_empty() {
+        return Ok(());
+    }
+
+    let filter = Filter::new()
+        .address(pool_index.keys().copied().collect::<Vec<Address>>())
+        .topic0(sync_topic())
+        .from_block(from_block)
+        .to_block(to_block);
+
+    let logs = client.get_logs(&filter).await?;
+    for log in logs {
+        let Some((factory, key)) = pool_index.get(&log.address).copied() else {
+            continue;
+        };
+        let decoded = decode(&[ParamType::Uint(112), ParamType::Uint(112)], &log.data)?;
+        let reserve0 = decoded[0]
+            .clone()
+            .into_uint()
+            .ok_or_else(|| anyhow!("Sync reserve0 not a uint"))?
+            .as_u128();
+        let reserve1 = decoded[1]
+            .clone()
+            .into_uint()
+            .ok_or_else(|| anyhow!("Sync reserve1 not a uint"))?
+            .as_u128();
+
+        let pair = match factory {
+            Some(factory_address) => protocols
+                .get_mut(&factory_address)
+                .and_then(|protocol| protocol.pairs.get_mut(&key)),
+            None => custom_pairs.get_mut(&key),
+        };
+        if let Some(pair) = pair {
+            pair.reserve0 = reserve0;
+            pair.reserve1 = reserve1;
+        }
+    }
+
+    Ok(())
+}
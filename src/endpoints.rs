@@ -0,0 +1,171 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use ethers::prelude::*;
+use futures::{FutureExt, StreamExt};
+use rustc_hash::FxHashMap;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+pub type WSClient = Arc<Provider<Ws>>;
+
+/// How long a pending-tx or block "first seen" timestamp is kept around
+/// waiting for slower endpoints to catch up, before being evicted so the
+/// dedup maps don't grow without bound across a long-running process.
+const LATENCY_WINDOW: Duration = Duration::from_secs(30);
+
+/// Running delivery-latency score for one configured endpoint: how far
+/// behind the fastest endpoint it tends to be when a pending transaction or
+/// new block reaches it. An endpoint with nothing to compare against (e.g.
+/// the only one configured) never accumulates samples and is treated as
+/// fastest by default.
+#[derive(Default)]
+pub struct EndpointStats {
+    latency_ms_sum: AtomicU64,
+    samples: AtomicU64,
+}
+
+impl EndpointStats {
+    fn record(&self, latency_ms: u64) {
+        self.latency_ms_sum.fetch_add(latency_ms, Ordering::Relaxed);
+        self.samples.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Average delivery latency relative to the fastest endpoint to see the
+    /// same pending transaction or block, in milliseconds. `0` while no
+    /// sample has been recorded yet, which also makes it the natural
+    /// tie-break value for an endpoint nothing has ever raced.
+    pub fn average_latency_ms(&self) -> u64 {
+        let samples = self.samples.load(Ordering::Relaxed);
+        if samples == 0 {
+            return 0;
+        }
+        self.latency_ms_sum.load(Ordering::Relaxed) / samples
+    }
+}
+
+/// One configured RPC endpoint: its own websocket connection plus a
+/// continuously updated delivery-latency score.
+pub struct Endpoint {
+    pub url: String,
+    pub client: WSClient,
+    pub stats: EndpointStats,
+}
+
+/// A set of configured endpoints, scored continuously on pending-tx and
+/// block delivery latency so the bot can subscribe to the mempool through
+/// whichever one is currently fastest while spreading ordinary reads (e.g.
+/// balance checks) across all of them instead of leaning on one connection
+/// for everything.
+pub struct EndpointPool {
+    endpoints: Vec<Endpoint>,
+    next_read: AtomicUsize,
+}
+
+impl EndpointPool {
+    /// Connects to every URL in `urls`, in order. `urls` must be non-empty;
+    /// connection failures are fatal, matching how the primary endpoint is
+    /// connected at startup today.
+    pub async fn connect(urls: &[String]) -> Result<Self> {
+        let mut endpoints = Vec::with_capacity(urls.len());
+        for url in urls {
+            let provider = Provider::connect(url.as_str()).await?;
+            endpoints.push(Endpoint {
+                url: url.clone(),
+                client: Arc::new(provider),
+                stats: EndpointStats::default(),
+            });
+        }
+        Ok(Self {
+            endpoints,
+            next_read: AtomicUsize::new(0),
+        })
+    }
+
+    /// The endpoint currently delivering pending transactions and blocks
+    /// fastest, based on latency observed so far. Falls back to the first
+    /// configured endpoint until there's enough history to tell them apart.
+    pub fn fastest(&self) -> &Endpoint {
+        self.endpoints
+            .iter()
+            .min_by_key(|endpoint| endpoint.stats.average_latency_ms())
+            .expect("EndpointPool is never constructed with zero endpoints")
+    }
+
+    pub fn fastest_client(&self) -> WSClient {
+        self.fastest().client.clone()
+    }
+
+    /// Round-robins across all configured endpoints for reads that don't
+    /// care about mempool freshness (balance checks, pair syncs), so the
+    /// fastest endpoint isn't monopolized by work the slower ones handle
+    /// just as well.
+    pub fn next_read_client(&self) -> WSClient {
+        let index = self.next_read.fetch_add(1, Ordering::Relaxed) % self.endpoints.len();
+        self.endpoints[index].client.clone()
+    }
+
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        self.endpoints.iter().map(|endpoint| endpoint.url.as_str())
+    }
+}
+
+/// Continuously scores every endpoint in `pool` by how long after the
+/// fastest endpoint it delivers the same pending transaction or new block.
+/// Runs for as long as the subscriptions stay open, i.e. for the lifetime of
+/// the bot. A single configured endpoint has nothing to race against, so
+/// this returns immediately in that case.
+pub async fn run_continuous_scoring(pool: Arc<EndpointPool>) {
+    if pool.endpoints.len() < 2 {
+        return;
+    }
+
+    let first_seen_txs: Arc<Mutex<FxHashMap<H256, Instant>>> =
+        Arc::new(Mutex::new(FxHashMap::default()));
+    let first_seen_blocks: Arc<Mutex<FxHashMap<U64, Instant>>> =
+        Arc::new(Mutex::new(FxHashMap::default()));
+
+    let mut tasks = Vec::with_capacity(pool.endpoints.len() * 2);
+    for endpoint in &pool.endpoints {
+        tasks.push(score_pending_txs(endpoint, first_seen_txs.clone()).boxed());
+        tasks.push(score_blocks(endpoint, first_seen_blocks.clone()).boxed());
+    }
+    futures::future::join_all(tasks).await;
+}
+
+async fn score_pending_txs(endpoint: &Endpoint, first_seen: Arc<Mutex<FxHashMap<H256, Instant>>>) {
+    let Ok(mut stream) = endpoint.client.subscribe_pending_txs().await else {
+        return;
+    };
+    while let Some(tx_hash) = stream.next().await {
+        let now = Instant::now();
+        let mut first_seen = first_seen.lock().await;
+        first_seen.retain(|_, seen_at| seen_at.elapsed() < LATENCY_WINDOW);
+        let first_seen_at = *first_seen.entry(tx_hash).or_insert(now);
+        drop(first_seen);
+        endpoint
+            .stats
+            .record(now.saturating_duration_since(first_seen_at).as_millis() as u64);
+    }
+}
+
+async fn score_blocks(endpoint: &Endpoint, first_seen: Arc<Mutex<FxHashMap<U64, Instant>>>) {
+    let Ok(mut stream) = endpoint.client.subscribe_blocks().await else {
+        return;
+    };
+    while let Some(block) = stream.next().await {
+        let Some(number) = block.number else {
+            continue;
+        };
+        let now = Instant::now();
+        let mut first_seen = first_seen.lock().await;
+        first_seen.retain(|_, seen_at| seen_at.elapsed() < LATENCY_WINDOW);
+        let first_seen_at = *first_seen.entry(number).or_insert(now);
+        drop(first_seen);
+        endpoint
+            .stats
+            .record(now.saturating_duration_since(first_seen_at).as_millis() as u64);
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+
+/// Coarse classification of a failure's root cause, attached to an
+/// `anyhow::Error` at the module boundary where it's first known (an RPC
+/// call, a decode path, a DB interaction, a swap-math computation, a
+/// submission) via `WithErrorCode::code`. Lets logs and whatever scrapes
+/// them tell "the provider is down" from "my own config is wrong" without
+/// parsing error message text.
+///
+/// This only covers the boundaries that have been wired up so far, not
+/// every `anyhow!`/`bail!` in the crate — `code_of` falls back to `Other`
+/// for anything that was never tagged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// A node/provider call failed or timed out: the RPC endpoint is the
+    /// suspect, not our own logic.
+    Rpc,
+    /// Decoding a pending transaction or on-chain log into our own types
+    /// failed, e.g. an unrecognized selector or a router ABI mismatch.
+    Decode,
+    /// A sqlite read or write failed.
+    Db,
+    /// A swap-math computation failed (overflow, underflow, no liquidity,
+    /// divide by zero, ...).
+    Math,
+    /// Simulating or submitting a transaction failed.
+    Execution,
+    /// Doesn't fit the above, e.g. malformed config or unexpected state.
+    Other,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Rpc => "RPC",
+            Self::Decode => "DECODE",
+            Self::Db => "DB",
+            Self::Math => "MATH",
+            Self::Execution => "EXECUTION",
+            Self::Other => "OTHER",
+        })
+    }
+}
+
+/// Wraps an error with the `ErrorCode` it was tagged with, so `code_of` can
+/// recover it later via `anyhow::Error::downcast_ref` without the original
+/// error type needing to know about error codes at all.
+#[derive(Debug)]
+struct Coded {
+    code: ErrorCode,
+    source: anyhow::Error,
+}
+
+impl fmt::Display for Coded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.code, self.source)
+    }
+}
+
+impl std::error::Error for Coded {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Tags a fallible result with an `ErrorCode` at the module boundary where
+/// its failure mode is known, without requiring that boundary to give up
+/// `anyhow::Result` for a bespoke error type.
+pub trait WithErrorCode<T> {
+    fn code(self, code: ErrorCode) -> anyhow::Result<T>;
+}
+
+impl<T, E> WithErrorCode<T> for Result<T, E>
+where
+    E: Into<anyhow::Error>,
+{
+    fn code(self, code: ErrorCode) -> anyhow::Result<T> {
+        self.map_err(|error| {
+            anyhow::Error::new(Coded {
+                code,
+                source: error.into(),
+            })
+        })
+    }
+}
+
+/// Recovers the `ErrorCode` an error was tagged with via `WithErrorCode`,
+/// defaulting to `ErrorCode::Other` for an error that was never tagged
+/// (e.g. one that failed before reaching a module boundary wired up to tag
+/// it).
+pub fn code_of(error: &anyhow::Error) -> ErrorCode {
+    error
+        .downcast_ref::<Coded>()
+        .map_or(ErrorCode::Other, |coded| coded.code)
+}
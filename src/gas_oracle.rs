@@ -0,0 +1,28 @@
+use anyhow::Result;
+use ethers::types::U256;
+
+use crate::errors::{ErrorCode, WithErrorCode};
+use crate::v2protocol::WSClient;
+
+/// Source of a gas price for our own transactions that aren't mirroring a
+/// victim trade's gas (e.g. the periodic gas top-up transfers in
+/// `ensure_gas_reserves`), so the pricing policy can change without
+/// touching call sites.
+#[derive(Debug, Clone, Copy)]
+pub enum GasPriceStrategy {
+    /// Use the node's `eth_gasPrice` estimate as-is.
+    NodeEstimate,
+    /// Use the node's estimate scaled by a basis-point multiplier, e.g.
+    /// `11000` for the node's estimate plus 10%.
+    NodeEstimateScaled(u32),
+}
+
+impl GasPriceStrategy {
+    pub async fn quote(self, client: WSClient) -> Result<U256> {
+        let base = client.get_gas_price().await.code(ErrorCode::Rpc)?;
+        Ok(match self {
+            Self::NodeEstimate => base,
+            Self::NodeEstimateScaled(bps) => base.saturating_mul(bps.into()) / U256::from(10000),
+        })
+    }
+}
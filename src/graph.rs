@@ -1,19 +1,36 @@
-use std::collections::hash_map::{Entry, Values};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::iter::{zip, Chain, FlatMap};
+use std::env;
+use std::iter::zip;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, ensure, Result};
 use ethers::prelude::Address;
-use ethers::types::U256;
+use ethers::types::{H256, U256};
+use ethers::utils::keccak256;
+use lazy_static::lazy_static;
 use petgraph::adj::DefaultIx;
 use petgraph::prelude::{EdgeIndex, EdgeRef, Graph, NodeIndex};
 use petgraph::{Outgoing, Undirected};
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
+use crate::errors::{ErrorCode, WithErrorCode};
 use crate::pair::Pair;
 use crate::v2protocol::Protocol;
 
-const MAX_NUM_SWAPS: usize = 4; // Num of tokens, therefore max pairs is 4
+pub const DEFAULT_MAX_NUM_SWAPS: usize = 4; // Num of tokens, therefore max pairs is 4
+
+lazy_static! {
+    /// Whether `create_graph` should synthesize an extra deepest-liquidity
+    /// edge for token pairs that exist on more than one protocol. Off by
+    /// default since it changes which of several equally-executable pools a
+    /// search settles on (by reserve depth, not by quoted price for the
+    /// search's own amount); protocols with only one pool per pair are
+    /// unaffected either way.
+    static ref AGGREGATE_DUPLICATE_PAIRS: bool = env::var("AGGREGATE_DUPLICATE_PAIRS")
+        .ok()
+        .map(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+}
 
 #[derive(Debug, Clone)]
 pub struct Path {
@@ -41,16 +58,21 @@ pub struct SearchPath {
     token_order: Vec<NodeIndex>,
     pair_order: Vec<EdgeIndex>,
     weight: U256,
+    /// `weight` normalized onto a 1e18 basis, used only for comparing
+    /// candidate paths; the raw `weight` is still what feeds the next hop's
+    /// `get_amount_out` so real swap math stays exact.
+    scaled_weight: U256,
 }
 
 impl SearchPath {
-    const fn new(weight: U256) -> Self {
+    fn new(weight: U256, scaled_weight: U256) -> Self {
         let token_order = Vec::new();
         let pair_order = Vec::new();
         Self {
             token_order,
             pair_order,
             weight,
+            scaled_weight,
         }
     }
 }
@@ -74,7 +96,10 @@ impl Path {
                 graph
                     .edge_weight(*edge)
                     .ok_or_else(|| anyhow!("Missing edge"))
-                    .map(|edge| PairLookup::new(edge.factory_address, edge.get_tokens()))
+                    .map(|edge| {
+                        let pair = edge.pair();
+                        PairLookup::new(pair.factory_address, pair.get_tokens())
+                    })
             })
             .collect::<Result<Vec<PairLookup>>>()?;
 
@@ -104,13 +129,84 @@ impl Path {
                     .get(&pair_key.pair_addresses)
                     .ok_or_else(|| anyhow!("Pair not found in protocol")),
             }?;
-            current_amount = pair.get_amount_out(*input, current_amount)?;
+            current_amount = pair
+                .get_amount_out(*input, current_amount)
+                .code(ErrorCode::Math)?;
             amounts.push(current_amount);
         }
 
         Ok(amounts)
     }
 
+    pub fn pool_addresses(
+        &self,
+        protocols: &HashMap<Address, Protocol>,
+        custom_pairs: &FxHashMap<(Address, Address), Pair>,
+    ) -> Result<Vec<Address>> {
+        self.pair_order
+            .iter()
+            .map(|lookup| {
+                let pair = match protocols.get(&lookup.factory_address) {
+                    None => custom_pairs
+                        .get(&lookup.pair_addresses)
+                        .ok_or_else(|| anyhow!("Pair not found in customs")),
+                    Some(protocol) => protocol
+                        .pairs
+                        .get(&lookup.pair_addresses)
+                        .ok_or_else(|| anyhow!("Pair not found in protocol")),
+                }?;
+                Ok(pair.contract.address())
+            })
+            .collect()
+    }
+
+    /// A deterministic identifier for this path's tokens and pools, used as
+    /// the cooldown key so a retried opportunity is recognized by the pools
+    /// it actually touches rather than just the tokens it routes through
+    /// (two paths can share a token order while swapping through different
+    /// pools). Stable across process restarts, unlike an in-memory `Instant`
+    /// map, so it doubles as the fingerprint persisted for replay protection.
+    pub fn fingerprint(&self) -> H256 {
+        let mut bytes =
+            Vec::with_capacity(self.token_order.len() * 20 + self.pair_order.len() * 40);
+        for token in &self.token_order {
+            bytes.extend_from_slice(token.as_bytes());
+        }
+        for pair in &self.pair_order {
+            bytes.extend_from_slice(pair.pair_addresses.0.as_bytes());
+            bytes.extend_from_slice(pair.pair_addresses.1.as_bytes());
+        }
+        H256::from(keccak256(bytes))
+    }
+
+    /// A rough liquidity-depth heuristic for the path: the shallowest pool's
+    /// combined reserves. Not normalized across token decimals or prices, so
+    /// it's only meaningful for comparing paths that are otherwise close on
+    /// expected value, not as an absolute measure of depth.
+    pub fn min_pool_liquidity(
+        &self,
+        protocols: &HashMap<Address, Protocol>,
+        custom_pairs: &FxHashMap<(Address, Address), Pair>,
+    ) -> Result<u128> {
+        self.pair_order
+            .iter()
+            .map(|lookup| {
+                let pair = match protocols.get(&lookup.factory_address) {
+                    None => custom_pairs
+                        .get(&lookup.pair_addresses)
+                        .ok_or_else(|| anyhow!("Pair not found in customs")),
+                    Some(protocol) => protocol
+                        .pairs
+                        .get(&lookup.pair_addresses)
+                        .ok_or_else(|| anyhow!("Pair not found in protocol")),
+                }?;
+                Ok(pair.reserve0.saturating_add(pair.reserve1))
+            })
+            .try_fold(u128::MAX, |min_so_far, reserves: Result<u128>| {
+                reserves.map(|reserves| min_so_far.min(reserves))
+            })
+    }
+
     pub fn get_amounts_in(
         &self,
         output: U256,
@@ -127,118 +223,276 @@ impl Path {
                 .pairs
                 .get(&pair_key.pair_addresses)
                 .ok_or_else(|| anyhow!("Pair not found in protocol"))?;
-            current_amount = pair.get_amount_in(*input, current_amount)?;
+            current_amount = pair
+                .get_amount_in(*input, current_amount)
+                .code(ErrorCode::Math)?;
             amounts.insert(0, current_amount);
         }
         Ok(amounts)
     }
 }
 
-type MyGraph<'a> = Graph<Address, &'a Pair, Undirected, DefaultIx>;
+/// A graph edge over one real, executable `Pair`. `Pool` is a genuine
+/// per-protocol pool, added for every pair the graph is built from.
+/// `DeepestLiquidity` is an extra edge added alongside the `Pool` edges of a
+/// token pair that exists on more than one protocol, pointing at whichever of
+/// those pools has the deepest combined reserves - a liquidity-depth
+/// heuristic, not a this-trade's-actual-output comparison, so a shallower
+/// pool with a better price for the search's amount can still lose out. It
+/// exists purely to give `get_successors` a single dominant branch to offer
+/// instead of every protocol's pool for that pair, so search doesn't waste
+/// hops exploring ones it's already decided are worse by this heuristic. Both
+/// variants wrap a real `&Pair`, so execution-facing code (`Path`,
+/// `get_amounts_out`, ...) resolves either one exactly the same way.
+#[derive(Debug, Copy, Clone)]
+enum GraphEdge<'a> {
+    Pool(&'a Pair),
+    DeepestLiquidity(&'a Pair),
+}
 
-fn add_pair<'a>(
-    graph: &mut MyGraph<'a>,
-    pair: &'a Pair,
-    nodes: &mut HashMap<Address, NodeIndex>,
-) -> Result<()> {
-    let (token0, token1) = pair.get_tokens();
-    let node0 = match nodes.get(&token0) {
-        Some(node) => *node,
-        None => {
-            let index = graph.add_node(token0);
-            nodes.insert(token0, index);
-            index
+impl<'a> GraphEdge<'a> {
+    fn pair(&self) -> &'a Pair {
+        match self {
+            GraphEdge::Pool(pair) | GraphEdge::DeepestLiquidity(pair) => pair,
         }
-    };
-
-    let node1 = match nodes.get(&token1) {
-        Some(node) => *node,
-        None => {
-            let index = graph.add_node(token1);
-            nodes.insert(token1, index);
-            index
+    }
+
+    fn calculate_weight(&self, node_token: Address, cur_weight: U256) -> U256 {
+        self.pair().calculate_weight(node_token, cur_weight)
+    }
+
+    fn normalize_to_1e18(&self, token: Address, amount: U256) -> U256 {
+        self.pair().normalize_to_1e18(token, amount)
+    }
+}
+
+type MyGraph<'a> = Graph<Address, GraphEdge<'a>, Undirected, DefaultIx>;
+
+/// Persistent token→node-index assignment for the shared pair graph, reused
+/// across repeated `create_graph` calls instead of being rehashed from
+/// scratch every time. The graph itself still has to be rebuilt per call
+/// (its edges borrow `&Pair` for the lifetime of that call, since reserves
+/// mutate outside of any lock the graph could hold across calls), but which
+/// token maps to which `NodeIndex` barely ever changes, so `create_graph`
+/// replays `order` to repopulate the fresh graph's nodes instead of building
+/// a new token map on every search. Only pair *additions* need to touch this via
+/// `register_pair`; a removed pair needs no corresponding call, since an
+/// orphaned token just becomes a node with no edges, which wastes a graph
+/// slot but changes no search result.
+#[derive(Debug, Clone, Default)]
+pub struct TokenIndex {
+    order: Vec<Address>,
+    lookup: FxHashMap<Address, NodeIndex>,
+}
+
+impl TokenIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn get_or_insert(&mut self, token: Address) -> NodeIndex {
+        if let Some(&index) = self.lookup.get(&token) {
+            return index;
         }
-    };
+        let index = NodeIndex::new(self.order.len());
+        self.order.push(token);
+        self.lookup.insert(token, index);
+        index
+    }
 
-    graph.add_edge(node0, node1, pair);
+    /// Registers both ends of `pair`, growing the index if either token
+    /// hasn't been seen before. Called whenever a pair is added to
+    /// `protocols`/`custom_pairs`, so the very next search doesn't have to
+    /// discover the new token itself.
+    pub fn register_pair(&mut self, pair: &Pair) {
+        let (token0, token1) = pair.get_tokens();
+        self.get_or_insert(token0);
+        self.get_or_insert(token1);
+    }
 
-    Ok(())
+    fn get(&self, token: &Address) -> Option<NodeIndex> {
+        self.lookup.get(token).copied()
+    }
+}
+
+fn add_pair<'a>(graph: &mut MyGraph<'a>, pair: &'a Pair, token_index: &mut TokenIndex) {
+    let (token0, token1) = pair.get_tokens();
+    let node0 = sync_node(graph, token_index, token0);
+    let node1 = sync_node(graph, token_index, token1);
+
+    graph.add_edge(node0, node1, GraphEdge::Pool(pair));
+}
+
+/// Looks `token` up in `token_index`, adding it to both `token_index` and
+/// `graph` if it's new. Keeps `graph`'s node count in lock-step with
+/// `token_index.order`'s length, which is what lets `create_graph` replay
+/// `order` directly into a fresh graph and trust the resulting `NodeIndex`es
+/// still line up.
+fn sync_node(graph: &mut MyGraph, token_index: &mut TokenIndex, token: Address) -> NodeIndex {
+    let before = token_index.order.len();
+    let index = token_index.get_or_insert(token);
+    if token_index.order.len() > before {
+        graph.add_node(token);
+    }
+    index
 }
 
 pub fn create_graph<'a>(
-    allpairs: Chain<
-        FlatMap<
-            Values<'a, ethers::types::H160, Protocol>,
-            Values<'a, (ethers::types::H160, ethers::types::H160), Pair>,
-            fn(&'a Protocol) -> Values<'a, (ethers::types::H160, ethers::types::H160), Pair>,
-        >,
-        Values<'a, (ethers::types::H160, ethers::types::H160), Pair>,
-    >,
-    nodes: &mut HashMap<Address, NodeIndex>,
+    allpairs: impl Iterator<Item = &'a Pair>,
+    token_index: &mut TokenIndex,
 ) -> Result<MyGraph<'a>> {
-    let mut graph: MyGraph = MyGraph::new_undirected();
+    let mut graph: MyGraph = MyGraph::with_capacity(token_index.order.len(), 0);
+    for &token in &token_index.order {
+        graph.add_node(token);
+    }
 
+    let aggregate = *AGGREGATE_DUPLICATE_PAIRS;
+    let mut duplicate_groups: FxHashMap<(Address, Address), Vec<&'a Pair>> = FxHashMap::default();
     for pair in allpairs {
-        add_pair(&mut graph, pair, nodes)?;
+        add_pair(&mut graph, pair, token_index);
+        if aggregate {
+            let (token0, token1) = pair.get_tokens();
+            let key = if token0 < token1 {
+                (token0, token1)
+            } else {
+                (token1, token0)
+            };
+            duplicate_groups.entry(key).or_default().push(pair);
+        }
+    }
+
+    for ((token0, token1), pairs) in duplicate_groups {
+        if pairs.len() < 2 {
+            continue;
+        }
+        let deepest = pairs
+            .into_iter()
+            .max_by_key(|pair| pair.reserve0.saturating_add(pair.reserve1))
+            .expect("Duplicate-pair group is never empty");
+        let node0 = sync_node(&mut graph, token_index, token0);
+        let node1 = sync_node(&mut graph, token_index, token1);
+        graph.add_edge(node0, node1, GraphEdge::DeepestLiquidity(deepest));
     }
+
     Ok(graph)
 }
 
+/// Searches for the best cycle back to any one of `targets`, e.g. a
+/// strategy's primary target plus whatever other base tokens it's willing to
+/// anchor a cycle on. Each candidate is resolved to its own real `NodeIndex`
+/// handle up front via `token_index`, so a multi-base-token search is just
+/// several real start/end nodes, rather than needing some sentinel address to
+/// stand in for "any of these" - the thing that made a single implicit
+/// target both load-bearing and fragile. Each candidate gets its own
+/// `search_visit` pass with its own pruning table: a `(node, hops)` state
+/// dominated while searching for goal A isn't necessarily dominated while
+/// searching for goal B, since the weight that reached that state depends on
+/// which start token the search began from. Only the final `best` result is
+/// compared across goals.
 pub fn find_shortest_path<'a>(
     graph: &MyGraph<'a>,
-    nodes: HashMap<Address, NodeIndex>,
-    target: &Address,
+    token_index: &TokenIndex,
+    targets: &[Address],
     amount_in: U256,
+    max_hops: usize,
 ) -> Result<Path> {
-    let goal = *nodes
-        .get(target)
-        .ok_or_else(|| anyhow!("Missing target node"))?;
+    ensure!(!targets.is_empty(), "No target tokens given");
+    let goals: Vec<NodeIndex> = targets
+        .iter()
+        .map(|target| {
+            token_index
+                .get(target)
+                .ok_or_else(|| anyhow!("Missing target node"))
+        })
+        .collect::<Result<_>>()?;
+
+    let mut best_path = SearchPath::new(0.into(), 0.into());
+    for goal in goals {
+        let start_scaled = scale_for_node(graph, goal, amount_in);
+        let mut start_path = SearchPath::new(amount_in, start_scaled);
+        start_path.token_order.push(goal);
+        let mut seen: HashMap<(NodeIndex, usize), U256> = HashMap::new();
+        search_visit(graph, goal, start_path, &mut seen, &mut best_path, max_hops);
+    }
+
+    Path::from_search_path(graph, best_path)
+}
 
+/// Like `find_shortest_path`, but between two distinct tokens rather than a
+/// loop back to the same token: used for one-off conversions (e.g. turning
+/// accumulated profit into native gas) rather than arbitrage search.
+pub fn find_conversion_path<'a>(
+    graph: &MyGraph<'a>,
+    token_index: &TokenIndex,
+    from: &Address,
+    to: &Address,
+    amount_in: U256,
+    max_hops: usize,
+) -> Result<Path> {
+    let start = token_index
+        .get(from)
+        .ok_or_else(|| anyhow!("Missing source node"))?;
+    let goal = token_index
+        .get(to)
+        .ok_or_else(|| anyhow!("Missing destination node"))?;
+
+    let start_scaled = scale_for_node(graph, start, amount_in);
     let mut seen: HashMap<(NodeIndex, usize), U256> = HashMap::new();
-    let mut best_path = SearchPath::new(0.into());
-    let mut start_path = SearchPath::new(amount_in);
-    start_path.token_order.push(goal);
-    search_visit(graph, goal, start_path, &mut seen, &mut best_path);
+    let mut best_path = SearchPath::new(0.into(), 0.into());
+    let mut start_path = SearchPath::new(amount_in, start_scaled);
+    start_path.token_order.push(start);
+    search_visit(graph, goal, start_path, &mut seen, &mut best_path, max_hops);
 
     Path::from_search_path(graph, best_path)
 }
 
+fn scale_for_node(graph: &MyGraph, node: NodeIndex, amount: U256) -> U256 {
+    let token = *graph.node_weight(node).unwrap();
+    match graph.edges_directed(node, Outgoing).next() {
+        Some(edge) => edge.weight().normalize_to_1e18(token, amount),
+        None => amount,
+    }
+}
+
 fn search_visit(
     graph: &MyGraph,
     target_node: NodeIndex,
     cur_path: SearchPath,
     seen_nodes: &mut HashMap<(NodeIndex, usize), U256>,
     best: &mut SearchPath,
+    max_hops: usize,
 ) {
-    if cur_path.pair_order.len() > MAX_NUM_SWAPS {
+    if cur_path.pair_order.len() > max_hops {
         return;
     }
     let cur_node = cur_path.token_order[cur_path.token_order.len() - 1];
     let cur_weight = cur_path.weight;
+    let cur_scaled_weight = cur_path.scaled_weight;
 
     if cur_node == target_node && !cur_path.pair_order.is_empty() {
-        if cur_weight > best.weight {
+        if cur_scaled_weight > best.scaled_weight {
             best.token_order = cur_path.token_order;
             best.pair_order = cur_path.pair_order;
             best.weight = cur_weight;
+            best.scaled_weight = cur_scaled_weight;
         }
         return;
     }
 
     match seen_nodes.entry((cur_node, cur_path.pair_order.len())) {
         Entry::Occupied(mut occupied) => {
-            if *occupied.get() > cur_weight {
+            if *occupied.get() > cur_scaled_weight {
                 return;
             } else {
-                occupied.insert(cur_weight);
+                occupied.insert(cur_scaled_weight);
             }
         }
         Entry::Vacant(vacant) => {
-            vacant.insert(cur_weight);
+            vacant.insert(cur_scaled_weight);
         }
     }
 
-    for (edge, node, weight) in get_successors(graph, cur_node, cur_weight) {
+    for (edge, node, weight, scaled_weight) in get_successors(graph, cur_node, cur_weight) {
         if cur_path.pair_order.contains(&edge) {
             continue;
         } else {
@@ -246,7 +500,8 @@ fn search_visit(
             next_path.pair_order.push(edge);
             next_path.token_order.push(node);
             next_path.weight = weight;
-            search_visit(graph, target_node, next_path, seen_nodes, best);
+            next_path.scaled_weight = scaled_weight;
+            search_visit(graph, target_node, next_path, seen_nodes, best, max_hops);
         }
     }
 }
@@ -255,17 +510,34 @@ fn get_successors(
     graph: &MyGraph,
     node: NodeIndex,
     cur_weight: U256,
-) -> Vec<(EdgeIndex, NodeIndex, U256)> {
+) -> Vec<(EdgeIndex, NodeIndex, U256, U256)> {
     let node_token = graph.node_weight(node).unwrap();
-    let edges = graph.edges_directed(node, Outgoing);
+    let edges: Vec<_> = graph.edges_directed(node, Outgoing).collect();
+
+    // A `DeepestLiquidity` edge stands in for every `Pool` edge sharing its
+    // target, so once one is present the other `Pool` edges are skipped
+    // rather than explored as separate branches.
+    let aggregated_targets: FxHashSet<NodeIndex> = edges
+        .iter()
+        .filter(|edge| matches!(edge.weight(), GraphEdge::DeepestLiquidity(_)))
+        .map(|edge| edge.target())
+        .collect();
 
     let mut successors = Vec::new();
 
     for edge in edges {
+        if aggregated_targets.contains(&edge.target())
+            && matches!(edge.weight(), GraphEdge::Pool(_))
+        {
+            continue;
+        }
+
         let target = edge.target();
+        let target_token = *graph.node_weight(target).unwrap();
         let weight = edge.weight().calculate_weight(*node_token, cur_weight);
+        let scaled_weight = edge.weight().normalize_to_1e18(target_token, weight);
 
-        successors.push((edge.id(), target, weight));
+        successors.push((edge.id(), target, weight, scaled_weight));
     }
 
     successors
@@ -3,6 +3,7 @@
 use std::collections::HashMap;
 use std::env;
 use std::ops::Div;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -13,9 +14,11 @@ use deadpool_sqlite::{Config, Pool, Runtime};
 use ethers::abi::Detokenize;
 use ethers::contract::abigen;
 use ethers::prelude::builders::ContractCall;
-use ethers::prelude::{Address, LocalWallet, Middleware, Signer, TransactionRequest, U256};
+use ethers::prelude::{
+    Address, LocalWallet, Middleware, Signer, SubscriptionStream, TransactionRequest, Ws, U256,
+};
 use ethers::types::transaction::eip2718::TypedTransaction;
-use ethers::types::TransactionReceipt;
+use ethers::types::{Block, TransactionReceipt, H256};
 use ethers::utils::parse_units;
 use futures::future::join_all;
 use futures::stream::StreamExt;
@@ -24,61 +27,385 @@ use lazy_static::lazy_static;
 use rustc_hash::FxHashMap;
 use tokio::time::Instant;
 
-use crate::pair::{generate_custom_pairs, Pair};
+use crate::approvals::ensure_router_approvals;
+use crate::bankroll::BankrollPolicy;
+use crate::builder::{compute_bribe, tip_builder};
+use crate::chain_profile::ChainProfile;
+use crate::cli::{
+    add_protocol, parse_add_protocol_args, parse_reconstruct_reserves_args, reconstruct_reserves,
+};
+use crate::contract_version::{build_attempt_arbitrage_call, detect_version, ArbContractVersion};
+use crate::cursor::{
+    apply_reserve_snapshot, collect_reserve_snapshot, load_cursor, load_reserve_snapshot,
+    replay_reserves_from_logs, save_cursor, save_reserve_snapshot, BlockCursor,
+};
+use crate::endpoints::EndpointPool;
+use crate::errors::{code_of, ErrorCode, WithErrorCode};
+use crate::gas_oracle::GasPriceStrategy;
+use crate::pair::{generate_custom_pairs, load_token_allowlist, Pair};
+use crate::rebalance::{plan_rebalance, RebalancePolicy};
+use crate::rpc_budget::RpcBudget;
+use crate::scoring::{EvConfig, RevertStats};
+use crate::settlement::{SubmissionRegistry, SubmissionStatus};
+use crate::strategy::{load_strategies, Strategy};
 use crate::trade::{Gas, PossibleArbitrage};
-use crate::txpool::TxPool;
-use crate::v2protocol::{generate_protocols, update_all_pairs, Protocol, WSClient};
+use crate::txpool::{ThrottleConfig, TxPool};
+use crate::v2protocol::{
+    audit_cross_protocol_quotes, audit_reserve_divergence, generate_protocols, update_all_pairs,
+    Protocol, WSClient,
+};
+use crate::wallet_strategy::{load_wallet_strategies, scale_gas, WalletStrategy};
 
+mod approvals;
+mod bankroll;
+mod builder;
+mod chain_profile;
+mod cli;
+mod contract_version;
+mod cursor;
+mod endpoints;
+mod errors;
+mod gas_oracle;
 mod graph;
 mod pair;
+mod quoting;
+mod rebalance;
+mod rpc_budget;
+mod scoring;
+mod settlement;
+mod snapshots;
+mod stats;
+mod strategy;
 mod trade;
 mod txpool;
 mod v2protocol;
+mod wallet_strategy;
 
 // const URL: &str = "wss://moonbeam.api.onfinality.io/ws?apikey=e1452126-1bc9-409a-b663-a7ae8e150c8b";
 
 lazy_static! {
     static ref URL: String = env::var("URL").unwrap();
+    /// Additional RPC endpoints, comma-separated, scored alongside `URL` so
+    /// the mempool subscription can automatically prefer whichever endpoint
+    /// is currently fastest. Unset means `URL` is the only endpoint, exactly
+    /// as before this existed.
+    static ref ENDPOINT_URLS: Option<String> = env::var("ENDPOINT_URLS").ok();
     static ref TRADED_TOKEN: String = env::var("TRADED").unwrap();
     static ref ARBITRAGE_CONTRACT: String = env::var("ARBITRAGE_CONTRACT").unwrap();
     static ref TRANSACTION_ATTEMPTS: u8 =
         u8::from_str(env::var("TX_ATTEMPTS").unwrap().as_str()).unwrap();
     static ref BALANCE_RESERVE: U256 =
         U256::from_dec_str(env::var("BALANCE_RESERVE").unwrap().as_str()).unwrap();
+    static ref BUILDER_BRIBE_BPS: Option<u32> = env::var("BUILDER_BRIBE_BPS")
+        .ok()
+        .map(|value| u32::from_str(value.as_str()).unwrap());
+    static ref QUOTE_SERVICE_ADDR: Option<std::net::SocketAddr> = env::var("QUOTE_SERVICE_ADDR")
+        .ok()
+        .map(|value| value.parse().unwrap());
+    static ref GAS_PRICE_STRATEGY: GasPriceStrategy = match env::var("GAS_PRICE_SCALE_BPS") {
+        Ok(value) => GasPriceStrategy::NodeEstimateScaled(u32::from_str(value.as_str()).unwrap()),
+        Err(_) => GasPriceStrategy::NodeEstimate,
+    };
+    static ref BANKROLL_POLICY: BankrollPolicy = match env::var("BANKROLL_MAX_NOTIONAL") {
+        Ok(value) => BankrollPolicy::FixedMax(U256::from_dec_str(value.as_str()).unwrap()),
+        Err(_) => match env::var("BANKROLL_FRACTION_BPS") {
+            Ok(value) => BankrollPolicy::Fraction(u32::from_str(value.as_str()).unwrap()),
+            Err(_) => BankrollPolicy::Full,
+        },
+    };
+    static ref RESERVE_AUDIT_SAMPLE_SIZE: usize = env::var("RESERVE_AUDIT_SAMPLE_SIZE")
+        .ok()
+        .map(|value| usize::from_str(value.as_str()).unwrap())
+        .unwrap_or(5);
+    static ref RESERVE_AUDIT_MAX_DRIFT_BPS: u32 = env::var("RESERVE_AUDIT_MAX_DRIFT_BPS")
+        .ok()
+        .map(|value| u32::from_str(value.as_str()).unwrap())
+        .unwrap_or(50);
+    /// How far a protocol's price for a pair it shares with other protocols
+    /// is allowed to diverge from their median before it counts against that
+    /// protocol as evidence of broken fee config or exotic fork math.
+    static ref PROTOCOL_QUOTE_MAX_DIVERGENCE_BPS: u32 =
+        env::var("PROTOCOL_QUOTE_MAX_DIVERGENCE_BPS")
+            .ok()
+            .map(|value| u32::from_str(value.as_str()).unwrap())
+            .unwrap_or(1000);
+    /// How far a chosen path's on-chain `getAmountsOut` result is allowed to
+    /// diverge from our own modeled output before we treat the opportunity
+    /// as evidence of a pricing bug and refuse to submit it.
+    static ref QUOTE_SANITY_MAX_DIVERGENCE_BPS: u32 = env::var("QUOTE_SANITY_MAX_DIVERGENCE_BPS")
+        .ok()
+        .map(|value| u32::from_str(value.as_str()).unwrap())
+        .unwrap_or(500);
+    /// Sentinel representing the chain's native asset (ETH) as a graph
+    /// node, since it has no address of its own. Defaults to the
+    /// conventional `0xEeee...EEeE` address used across the ecosystem.
+    static ref NATIVE_TOKEN_ADDRESS: Address = env::var("NATIVE_TOKEN_ADDRESS")
+        .ok()
+        .map(|value| Address::from_str(value.as_str()).unwrap())
+        .unwrap_or_else(|| {
+            Address::from_str("0xEeeeeEeeeEeEeeEeEeEeeEEEeeeeEeeeeeeeEEeE").unwrap()
+        });
+    /// Largest gap (in blocks) between a persisted reserve snapshot and the
+    /// current block we'll bridge via `Sync`-log replay on boot. Beyond this
+    /// a full multicall refresh is both cheaper and safer than trusting a
+    /// stale snapshot plus a huge log window.
+    static ref MAX_LOG_REPLAY_GAP: u64 = env::var("MAX_LOG_REPLAY_GAP")
+        .ok()
+        .map(|value| u64::from_str(value.as_str()).unwrap())
+        .unwrap_or(200);
+    /// How long after startup (or after a `TxPool` rebuild) we keep
+    /// observing the mempool and refreshing reserves without submitting
+    /// anything, so we don't trade against a pair set that's still being
+    /// populated.
+    static ref WARMUP_DURATION: Duration = env::var("WARMUP_DURATION_SECS")
+        .ok()
+        .map(|value| Duration::from_secs(u64::from_str(value.as_str()).unwrap()))
+        .unwrap_or(Duration::from_secs(30));
+    /// How close to the chain's average block interval we have to be,
+    /// without having seen a new header yet, before a fresh search/
+    /// submission pass is skipped for this iteration. A search started this
+    /// close to the next block routinely finishes and submits after that
+    /// block has already landed, donating gas against reserves the arb no
+    /// longer holds for.
+    static ref BLOCK_IMMINENT_MARGIN: Duration = env::var("BLOCK_IMMINENT_MARGIN_MS")
+        .ok()
+        .map(|value| Duration::from_millis(u64::from_str(value.as_str()).unwrap()))
+        .unwrap_or(Duration::from_millis(300));
+    static ref EV_CONFIG: EvConfig = EvConfig {
+        inclusion_probability_bps: env::var("EV_INCLUSION_PROBABILITY_BPS")
+            .ok()
+            .map(|value| u32::from_str(value.as_str()).unwrap())
+            .unwrap_or(9000),
+        competition_win_probability_bps: env::var("EV_COMPETITION_WIN_PROBABILITY_BPS")
+            .ok()
+            .map(|value| u32::from_str(value.as_str()).unwrap())
+            .unwrap_or(5000),
+    };
+    /// Caps the size of the standalone reserve-scan probe run after each
+    /// block's reserve refresh (see `find_opportunistic_arbitrages`); unset
+    /// (the default) disables the scan entirely, leaving opportunities to
+    /// arise only from simulating pending transactions as before.
+    static ref OPPORTUNISTIC_SCAN_AMOUNT: Option<U256> = env::var("OPPORTUNISTIC_SCAN_AMOUNT")
+        .ok()
+        .map(|value| U256::from_dec_str(value.as_str()).unwrap());
+    /// Target share (in basis points) of the contract's working capital to
+    /// hold as native gas token versus the traded token; `None` (the
+    /// variable unset) disables automatic rebalancing entirely, leaving gas
+    /// top-ups to `ensure_gas_reserves`'s fixed reserve as before.
+    static ref REBALANCE_POLICY: Option<RebalancePolicy> = env::var("REBALANCE_TARGET_NATIVE_BPS")
+        .ok()
+        .map(|value| RebalancePolicy {
+            target_native_bps: u32::from_str(value.as_str()).unwrap(),
+            tolerance_bps: env::var("REBALANCE_TOLERANCE_BPS")
+                .ok()
+                .map(|value| u32::from_str(value.as_str()).unwrap())
+                .unwrap_or(1000),
+            max_hops: env::var("REBALANCE_MAX_HOPS")
+                .ok()
+                .map(|value| usize::from_str(value.as_str()).unwrap())
+                .unwrap_or(graph::DEFAULT_MAX_NUM_SWAPS),
+            slippage_bps: env::var("REBALANCE_SLIPPAGE_BPS")
+                .ok()
+                .map(|value| u32::from_str(value.as_str()).unwrap())
+                .unwrap_or(100),
+        });
+    /// Candidates whose expected value is within this many basis points of
+    /// the best one found for a strategy are considered tied and broken by
+    /// `select_tied_arbitrage`'s shorter-path/higher-liquidity/lower-competition order
+    /// instead of raw expected value, since EV is noisy enough that a
+    /// strictly-larger-by-a-few-wei winner isn't meaningfully better.
+    static ref EV_TIE_EPSILON_BPS: u32 = env::var("EV_TIE_EPSILON_BPS")
+        .ok()
+        .map(|value| u32::from_str(value.as_str()).unwrap())
+        .unwrap_or(200);
+    /// Minimum size of a victim transaction's first-hop input, as a share of
+    /// that pool's combined reserves in basis points, below which it's
+    /// dropped before the simulate/search stage as too small to be worth
+    /// chasing. Unset disables this filter, leaving
+    /// `min_trade_sizes.json`'s per-token absolute minimums (if any) as the
+    /// only size filter.
+    static ref MIN_VICTIM_TRADE_RESERVE_BPS: Option<u32> = env::var("MIN_VICTIM_TRADE_RESERVE_BPS")
+        .ok()
+        .map(|value| u32::from_str(value.as_str()).unwrap());
+    /// Hard ceiling on submissions attempted within a single block, a single
+    /// trailing-60-second window, and concurrently unresolved, respectively.
+    /// Unset (the default for each) leaves that particular cap off, so a
+    /// burst of correlated "profitable" signals — often a symptom of bad
+    /// data — can trigger as many transactions as the rest of the pipeline
+    /// allows, same as before this existed.
+    static ref SUBMISSION_THROTTLE: ThrottleConfig = ThrottleConfig {
+        max_per_block: env::var("MAX_SUBMISSIONS_PER_BLOCK")
+            .ok()
+            .map(|value| usize::from_str(value.as_str()).unwrap()),
+        max_per_minute: env::var("MAX_SUBMISSIONS_PER_MINUTE")
+            .ok()
+            .map(|value| usize::from_str(value.as_str()).unwrap()),
+        max_concurrent: env::var("MAX_CONCURRENT_SUBMISSIONS")
+            .ok()
+            .map(|value| usize::from_str(value.as_str()).unwrap()),
+    };
+    static ref RPC_BUDGET: RpcBudget = RpcBudget::new(
+        env::var("RPC_GLOBAL_CONCURRENCY")
+            .ok()
+            .map(|value| usize::from_str(value.as_str()).unwrap())
+            .unwrap_or(32),
+        env::var("RPC_PER_PROTOCOL_CONCURRENCY")
+            .ok()
+            .map(|value| usize::from_str(value.as_str()).unwrap())
+            .unwrap_or(8),
+    );
+}
+
+static REVERT_STATS: RevertStats = RevertStats::new();
+
+/// Directory every data file below is resolved against, unless a file has
+/// its own path override set. Defaults to the working directory, matching
+/// this bot's behavior before `DATA_DIR` existed, but lets an operator
+/// point config, state, and the binary at separate locations — useful on
+/// Windows and other platforms where "just run it from the repo root"
+/// isn't a natural deployment shape.
+fn data_dir() -> String {
+    env::var("DATA_DIR").unwrap_or_else(|_| ".".to_string())
+}
+
+/// Resolves a data file's path: `env_var`'s value if set (an explicit
+/// per-file override), otherwise `default_name` joined onto `DATA_DIR`.
+fn data_file_path(env_var: &str, default_name: &str) -> String {
+    env::var(env_var).unwrap_or_else(|_| {
+        Path::new(&data_dir())
+            .join(default_name)
+            .to_string_lossy()
+            .into_owned()
+    })
+}
+
+lazy_static! {
+    static ref PROTOCOLS_PATH: String = data_file_path("PROTOCOLS_PATH", "protocols.json");
+    static ref DB_PATH: String = data_file_path("DB_PATH", "pair_data.db");
+    static ref CUSTOM_PAIRS: String = data_file_path("CUSTOM_PAIRS", "custom_pairs.json");
+    static ref WALLET_STRATEGIES_PATH: String =
+        data_file_path("WALLET_STRATEGIES_PATH", "wallet_strategies.json");
+    static ref PAIR_BLACKLIST_PATH: String =
+        data_file_path("PAIR_BLACKLIST_PATH", "pair_blacklist.json");
+    static ref TOKEN_ALLOWLIST_PATH: String =
+        data_file_path("TOKEN_ALLOWLIST_PATH", "token_allowlist.json");
+    static ref MIN_TRADE_SIZES_PATH: String =
+        data_file_path("MIN_TRADE_SIZES_PATH", "min_trade_sizes.json");
+    static ref CURSOR_PATH: String = data_file_path("CURSOR_PATH", "cursor.json");
+    static ref RESERVE_SNAPSHOT_PATH: String =
+        data_file_path("RESERVE_SNAPSHOT_PATH", "reserve_snapshot.json");
+    static ref STRATEGIES_PATH: String = data_file_path("STRATEGIES_PATH", "strategies.json");
+    static ref ROUTER_MAP: String = data_file_path("ROUTER_MAP", "router_mappings.json");
+    static ref BAD_TOKENS_PATH: String = data_file_path("BAD_TOKENS_PATH", "bad_tokens.json");
+    /// Directory fetched-and-cached contract ABIs are written to, for
+    /// protocols whose `protocols.json` entry doesn't point at a local ABI
+    /// file directly.
+    static ref ABI_CACHE_DIR: String = data_file_path("ABI_CACHE_DIR", "abis");
 }
 
-const PROTOCOLS_PATH: &str = "protocols.json";
-const DB_PATH: &str = "pair_data.db";
-const CUSTOM_PAIRS: &str = "custom_pairs.json";
 const GAS_ESTIMATE: u32 = 500000;
+/// How often to run the reserve divergence audit against a fresh on-chain
+/// read, independent of the per-block reserve refresh.
+const RESERVE_AUDIT_INTERVAL: Duration = Duration::from_secs(300);
+/// How often to report per-router decoding coverage, so a router upgrade
+/// that starts silently dropping our transaction decoder shows up quickly
+/// without flooding the log on every block.
+const DECODING_COVERAGE_REPORT_INTERVAL: Duration = Duration::from_secs(600);
+/// Coverage below this is reported even outside the interval's own summary
+/// line, since a router this degraded is worth flagging as soon as we know.
+const DECODING_COVERAGE_WARN_BPS: u32 = 9000;
+/// How often to reconcile the in-memory `balances` map against a fresh
+/// `balance_of` call, to correct any drift accumulated by deriving balances
+/// from receipt logs on the hot path instead of querying the chain directly.
+const BALANCE_RECONCILE_INTERVAL: Duration = Duration::from_secs(900);
+/// How often to report cumulative reverted-attempt gas burn per wallet, so a
+/// wallet stuck in a revert loop shows up before it runs dry.
+const WALLET_GAS_BURN_REPORT_INTERVAL: Duration = Duration::from_secs(600);
+/// How often to refresh the per-pair revert/slippage penalty factors used as
+/// `get_profitable_arbitrages`'s competition tie-breaker; these change slowly
+/// relative to reserves, so a per-block reload isn't worth the DB round trip.
+const PENALTY_FACTOR_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+/// How often to scan `tx_pool.submission_registry` for submissions still
+/// `Pending`, so one that's stuck (the node lost track of it, or it's being
+/// held up by a nonce gap) shows up as a stale alert rather than silently
+/// dropping out of sight once `execute_trade` that broadcast it returns.
+const STUCK_SUBMISSION_REPORT_INTERVAL: Duration = Duration::from_secs(300);
+/// How often to persist a `ReserveSnapshot` to disk, so a restart has a
+/// recent real baseline to replay `Sync` logs onto instead of falling back
+/// to a full multicall refresh every time.
+const RESERVE_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(300);
+/// How often to check whether the contract's native/traded-token inventory
+/// has drifted from `REBALANCE_POLICY`'s target ratio.
+const REBALANCE_INTERVAL: Duration = Duration::from_secs(600);
 
 abigen!(erc20, "abis/erc20.json");
 abigen!(ArbContract, "abis/ArbContract.json");
 
+/// Fails fast with a clear message naming the resolved path when a data
+/// file the bot can't start without (`protocols.json`, `custom_pairs.json`)
+/// is missing, instead of a panic deep inside whichever task reads it
+/// first. Also creates `ABI_CACHE_DIR` if it doesn't exist yet, since
+/// that one is written to rather than just read.
+async fn ensure_data_layout() {
+    for (name, path) in [
+        ("protocols.json", PROTOCOLS_PATH.as_str()),
+        ("custom_pairs.json", CUSTOM_PAIRS.as_str()),
+    ] {
+        if tokio::fs::metadata(path).await.is_err() {
+            panic!(
+                "Missing required data file {}: expected at {:?} (DATA_DIR={:?}); create it or set its own path override env var",
+                name, path, data_dir()
+            );
+        }
+    }
+    tokio::fs::create_dir_all(ABI_CACHE_DIR.as_str())
+        .await
+        .expect("Failed to create ABI cache directory");
+}
+
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().expect("MISSING .env FILE");
+    ensure_data_layout().await;
 
-    let provider = ethers::providers::Provider::connect(URL.as_str())
-        .await
-        .unwrap();
-    let client = Arc::new(provider);
+    let mut endpoint_urls = vec![URL.clone()];
+    if let Some(extra) = ENDPOINT_URLS.as_ref() {
+        endpoint_urls.extend(extra.split(',').map(str::trim).map(String::from));
+    }
+    let endpoint_pool = Arc::new(EndpointPool::connect(&endpoint_urls).await.unwrap());
+    tokio::spawn(endpoints::run_continuous_scoring(endpoint_pool.clone()));
+    let client = endpoint_pool.fastest_client();
     let provider_ref = client.as_ref();
-    let cfg = Config::new(DB_PATH);
+    let cfg = Config::new(DB_PATH.as_str());
     let pool = Arc::new(cfg.create_pool(Runtime::Tokio1).unwrap());
+    stats::ensure_schema(&pool).await.unwrap();
+
+    let cli_args: Vec<String> = env::args().collect();
+    if cli_args.get(1).map(String::as_str) == Some("add-protocol") {
+        let parsed = parse_add_protocol_args(&cli_args[2..]).unwrap();
+        add_protocol(parsed, client, pool).await.unwrap();
+        return;
+    }
+    if cli_args.get(1).map(String::as_str) == Some("reconstruct-reserves") {
+        let parsed = parse_reconstruct_reserves_args(&cli_args[2..]).unwrap();
+        reconstruct_reserves(parsed, client, pool).await.unwrap();
+        return;
+    }
 
-    let traded_token: erc20<WSClient> = erc20::new(
-        Address::from_str(TRADED_TOKEN.as_str()).unwrap(),
-        Arc::new(client.clone()),
-    );
     let arbitrage_contract: ArbContract<WSClient> = ArbContract::new(
         Address::from_str(ARBITRAGE_CONTRACT.as_str()).unwrap(),
         Arc::new(client.clone()),
     );
+    let arb_contract_version = detect_version(&arbitrage_contract)
+        .await
+        .expect("Deployed ArbContract's interface version is unsupported by this build");
 
     let (main_wallet, other_wallets) = get_wallets().unwrap();
+    let wallet_strategies = load_wallet_strategies(WALLET_STRATEGIES_PATH.as_str())
+        .await
+        .unwrap();
     ensure_gas_reserves(
-        client.clone(),
+        &endpoint_pool,
         &main_wallet,
         &other_wallets,
         &arbitrage_contract,
@@ -86,57 +413,492 @@ async fn main() {
     .await
     .unwrap();
 
-    let mut balance_to_spend = traded_token
-        .balance_of(arbitrage_contract.address())
-        .call()
+    let token_allowlist = load_token_allowlist(TOKEN_ALLOWLIST_PATH.as_str())
         .await
         .unwrap();
+    let strategies = load_strategies(
+        STRATEGIES_PATH.as_str(),
+        Address::from_str(TRADED_TOKEN.as_str()).unwrap(),
+        *EV_CONFIG,
+        &other_wallets,
+        token_allowlist.as_ref(),
+    )
+    .await
+    .unwrap();
+
+    let mut balances = token_balances(&endpoint_pool, &arbitrage_contract, &strategies).await;
+
+    let chain_id = client.get_chainid().await.unwrap();
+    let chain_profile = chain_profile::for_chain_id(chain_id);
 
     let mut block_subscription = client.subscribe_blocks().await.unwrap();
     let mut last_update_time = Instant::now();
-    let mut tx_pool = TxPool::new(client.clone(), provider_ref, pool.clone())
-        .await
-        .unwrap();
-    tx_pool.get_all_reserves().await.unwrap();
-    let chain_id = client.get_chainid().await.unwrap();
+    let mut tx_pool = TxPool::new(
+        client.clone(),
+        provider_ref,
+        pool.clone(),
+        chain_profile,
+        *SUBMISSION_THROTTLE,
+    )
+    .await
+    .unwrap();
+    let mut warmup_until = Instant::now() + *WARMUP_DURATION;
+    let mut warmed_up = false;
+    println!("Warming up for {:?} before submitting any trades", *WARMUP_DURATION);
+    let mut cursor = load_cursor(CURSOR_PATH.as_str()).await;
+    let current_block = client.get_block_number().await.unwrap().as_u64();
+    // `pair_data.db` only caches pair addresses/tokens, not reserves, so
+    // every pair starts this process at reserve0 = reserve1 = 0. Replaying
+    // `Sync` logs directly onto that zeroed state would only patch pairs
+    // that happened to trade during the gap and leave every other pair
+    // looking drained, so a persisted `ReserveSnapshot` is the only valid
+    // baseline to replay onto; without one (or if it's gone too stale), a
+    // full refresh is the only way to get a real baseline after a restart.
+    match load_reserve_snapshot(RESERVE_SNAPSHOT_PATH.as_str()).await {
+        Some(snapshot)
+            if current_block.saturating_sub(snapshot.block_number) <= *MAX_LOG_REPLAY_GAP =>
+        {
+            apply_reserve_snapshot(&mut tx_pool.protocols, &mut tx_pool.custom_pairs, &snapshot);
+            replay_reserves_from_logs(
+                client.clone(),
+                &mut tx_pool.protocols,
+                &mut tx_pool.custom_pairs,
+                snapshot.block_number + 1,
+                current_block,
+            )
+            .await
+            .unwrap();
+        }
+        _ => tx_pool.get_all_reserves().await.unwrap(),
+    }
+    cursor.last_processed_block = Some(current_block);
+    cursor.last_reserve_sync_block = Some(current_block);
+    save_cursor(CURSOR_PATH.as_str(), cursor).await.unwrap();
+    ensure_router_approvals(
+        client.clone(),
+        &arbitrage_contract,
+        &main_wallet,
+        &tx_pool.protocols,
+        &tx_pool.custom_pairs,
+    )
+    .await
+    .unwrap();
+
+    if let Some(addr) = *QUOTE_SERVICE_ADDR {
+        let quote_client = client.clone();
+        let quote_pool = pool.clone();
+        tokio::spawn(async move {
+            if let Err(error) = quoting::serve(quote_client, quote_pool, addr).await {
+                println!("Quoting service stopped: {}", error);
+            }
+        });
+    }
+
+    let mut last_audit_time = Instant::now();
+    let mut last_rebalance_time = Instant::now();
+    let mut last_decoding_report_time = Instant::now();
+    let mut last_balance_reconcile_time = Instant::now();
+    let mut last_wallet_gas_burn_report_time = Instant::now();
+    let mut last_penalty_refresh_time = Instant::now();
+    let mut last_stuck_submission_report_time = Instant::now();
+    let mut last_reserve_snapshot_time = Instant::now();
+    let mut penalty_factors = stats::load_penalty_factors(&pool).await.unwrap_or_default();
+    let mut block_timing = BlockTiming::new();
     loop {
         if last_update_time.elapsed() > Duration::from_secs(3600) {
             last_update_time = Instant::now();
-            tx_pool = TxPool::new(client.clone(), provider_ref, pool.clone())
-                .await
-                .unwrap();
+            tx_pool = TxPool::new(
+                client.clone(),
+                provider_ref,
+                pool.clone(),
+                chain_profile,
+                *SUBMISSION_THROTTLE,
+            )
+            .await
+            .unwrap();
             tx_pool.get_all_reserves().await.unwrap();
+            warmup_until = Instant::now() + *WARMUP_DURATION;
+            warmed_up = false;
+            println!(
+                "TxPool rebuilt, warming up for {:?} before submitting any trades",
+                *WARMUP_DURATION
+            );
         } else if let Some(block) = block_subscription.next().now_or_never() {
-            tx_pool.get_all_reserves().await.unwrap();
-            let tx_hashes = block.expect("No block?").transactions;
-            tx_pool.remove_done_trades(tx_hashes).await.unwrap();
-            tx_pool.mark_unsimulated();
-            println!("Got new reserves");
+            let block = block.expect("No block?");
+            process_new_block(&mut tx_pool, &mut cursor, &mut block_timing, block).await;
         }
 
-        let profitable_trade = get_profitable_arbitrage(&mut tx_pool, balance_to_spend).await;
+        if last_audit_time.elapsed() > RESERVE_AUDIT_INTERVAL {
+            last_audit_time = Instant::now();
+            match audit_reserve_divergence(
+                &mut tx_pool.protocols,
+                *RESERVE_AUDIT_SAMPLE_SIZE,
+                *RESERVE_AUDIT_MAX_DRIFT_BPS,
+            )
+            .await
+            {
+                Ok(corrected) if corrected > 0 => {
+                    println!("Reserve audit corrected {} diverged pair(s)", corrected)
+                }
+                Ok(_) => {}
+                Err(error) => println!("Reserve audit failed: {}", error),
+            }
 
-        match profitable_trade {
-            None => continue,
-            Some(trade) => {
-                execute_trade(
-                    trade,
-                    client.clone(),
+            for suspended in audit_cross_protocol_quotes(
+                &mut tx_pool.protocols,
+                *PROTOCOL_QUOTE_MAX_DIVERGENCE_BPS,
+            ) {
+                println!(
+                    "Suspended protocol {} from routing: its quotes diverge from peer protocols \
+                     for the same pairs by more than {} bps",
+                    suspended, *PROTOCOL_QUOTE_MAX_DIVERGENCE_BPS
+                );
+            }
+        }
+
+        if last_decoding_report_time.elapsed() > DECODING_COVERAGE_REPORT_INTERVAL {
+            last_decoding_report_time = Instant::now();
+            for (router, coverage_bps, unknown_selector, type_mismatch, not_mapped) in
+                tx_pool.decoding_coverage()
+            {
+                let flag = if coverage_bps < DECODING_COVERAGE_WARN_BPS {
+                    " [DEGRADED]"
+                } else {
+                    ""
+                };
+                println!(
+                    "Decoding coverage for router {:?}: {}.{:02}%{} (unknown selector: {}, type mismatch: {}, not mapped: {})",
+                    router,
+                    coverage_bps / 100,
+                    coverage_bps % 100,
+                    flag,
+                    unknown_selector,
+                    type_mismatch,
+                    not_mapped
+                );
+            }
+        }
+
+        if last_balance_reconcile_time.elapsed() > BALANCE_RECONCILE_INTERVAL {
+            last_balance_reconcile_time = Instant::now();
+            reconcile_balances(&endpoint_pool, &arbitrage_contract, &mut balances).await;
+        }
+
+        if last_penalty_refresh_time.elapsed() > PENALTY_FACTOR_REFRESH_INTERVAL {
+            last_penalty_refresh_time = Instant::now();
+            match stats::load_penalty_factors(&pool).await {
+                Ok(refreshed) => penalty_factors = refreshed,
+                Err(error) => println!("Failed to refresh pair penalty factors: {}", error),
+            }
+        }
+
+        if last_wallet_gas_burn_report_time.elapsed() > WALLET_GAS_BURN_REPORT_INTERVAL {
+            last_wallet_gas_burn_report_time = Instant::now();
+            match stats::load_wallet_gas_burn(&pool).await {
+                Ok(burns) => {
+                    for (wallet, reverted_attempts, reverted_gas_wei_sum) in burns {
+                        println!(
+                            "Wallet {:?} has burned {} wei in gas across {} reverted attempt(s)",
+                            wallet, reverted_gas_wei_sum, reverted_attempts
+                        );
+                    }
+                }
+                Err(error) => println!("Failed to load wallet gas burn stats: {}", error),
+            }
+        }
+
+        if last_stuck_submission_report_time.elapsed() > STUCK_SUBMISSION_REPORT_INTERVAL {
+            last_stuck_submission_report_time = Instant::now();
+            let stuck: Vec<H256> = tx_pool
+                .submission_registry
+                .submissions()
+                .into_iter()
+                .filter(|submission| submission.status == SubmissionStatus::Pending)
+                .map(|submission| submission.tx_hash)
+                .collect();
+            if !stuck.is_empty() {
+                println!(
+                    "{} submission(s) still pending after {:?}: {:?}",
+                    stuck.len(),
+                    STUCK_SUBMISSION_REPORT_INTERVAL,
+                    stuck
+                );
+            }
+        }
+
+        if last_reserve_snapshot_time.elapsed() > RESERVE_SNAPSHOT_INTERVAL {
+            last_reserve_snapshot_time = Instant::now();
+            if let Some(block_number) = cursor.last_processed_block {
+                let snapshot = collect_reserve_snapshot(
                     &tx_pool.protocols,
                     &tx_pool.custom_pairs,
+                    block_number,
+                );
+                if let Err(error) =
+                    save_reserve_snapshot(RESERVE_SNAPSHOT_PATH.as_str(), &snapshot).await
+                {
+                    println!("Failed to persist reserve snapshot: {}", error);
+                }
+            }
+        }
+
+        if let Some(policy) = *REBALANCE_POLICY {
+            if last_rebalance_time.elapsed() > REBALANCE_INTERVAL {
+                last_rebalance_time = Instant::now();
+                match maybe_rebalance(
+                    client.clone(),
                     &arbitrage_contract,
-                    &other_wallets,
+                    arb_contract_version,
+                    &main_wallet,
+                    &tx_pool.protocols,
+                    &tx_pool.custom_pairs,
+                    Address::from_str(TRADED_TOKEN.as_str()).unwrap(),
+                    *NATIVE_TOKEN_ADDRESS,
                     chain_id,
+                    policy,
                 )
                 .await
-                .unwrap();
+                {
+                    Ok(true) => println!("Rebalanced inventory toward target native ratio"),
+                    Ok(false) => {}
+                    Err(error) => println!("Rebalance failed: {}", error),
+                }
+            }
+        }
+
+        if block_timing.next_block_imminent(*BLOCK_IMMINENT_MARGIN) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            continue;
+        }
 
-                balance_to_spend = traded_token
-                    .balance_of(arbitrage_contract.address())
-                    .call()
+        let inputs: Vec<U256> = strategies
+            .iter()
+            .map(|strategy| BANKROLL_POLICY.cap(balances[&strategy.target]))
+            .collect();
+        let mut profitable_trades = get_profitable_arbitrages(
+            &mut tx_pool,
+            &strategies,
+            &inputs,
+            &penalty_factors,
+            &mut block_subscription,
+            &mut cursor,
+            &mut block_timing,
+        )
+        .await;
+
+        if let Some(scan_amount) = *OPPORTUNISTIC_SCAN_AMOUNT {
+            match GAS_PRICE_STRATEGY.quote(client.clone()).await {
+                Ok(gas_price) => {
+                    let revert_probability_bps = REVERT_STATS.revert_probability_bps();
+                    let scan_inputs: Vec<U256> =
+                        inputs.iter().map(|input| (*input).min(scan_amount)).collect();
+                    let opportunistic = tx_pool.find_opportunistic_arbitrages(
+                        &strategies,
+                        &scan_inputs,
+                        Gas::Legacy(gas_price),
+                    );
+                    for ((strategy, existing), candidate) in
+                        strategies.iter().zip(profitable_trades.iter_mut()).zip(opportunistic)
+                    {
+                        let Some(candidate) = candidate else { continue };
+                        let candidate_ev =
+                            candidate.expected_value(strategy.ev_config, revert_probability_bps);
+                        if candidate_ev <= 0 {
+                            continue;
+                        }
+                        let better_than_existing = match existing {
+                            Some(current) => {
+                                candidate_ev
+                                    > current.expected_value(strategy.ev_config, revert_probability_bps)
+                            }
+                            None => true,
+                        };
+                        if better_than_existing {
+                            *existing = Some(candidate);
+                        }
+                    }
+                }
+                Err(error) => println!("Opportunistic scan gas quote failed: {}", error),
+            }
+        }
+
+        if Instant::now() < warmup_until {
+            continue;
+        } else if !warmed_up {
+            warmed_up = true;
+            println!("Warmup complete, submitting trades from now on");
+        }
+
+        // Rank every strategy's candidate by expected value instead of
+        // executing strategies in declaration order: with several
+        // strategies profitable in the same iteration, the highest-value
+        // one should claim contested pools first rather than losing them to
+        // whichever strategy happened to come first in `strategies`. Lower
+        // ones that turn out to want the same pools are preempted below
+        // instead of being submitted anyway.
+        let revert_probability_bps = REVERT_STATS.revert_probability_bps();
+        let mut ranked_trades: Vec<(&Strategy, PossibleArbitrage)> = strategies
+            .iter()
+            .zip(profitable_trades)
+            .filter_map(|(strategy, candidate)| candidate.map(|trade| (strategy, trade)))
+            .collect();
+        ranked_trades.sort_by_key(|(strategy, trade)| {
+            std::cmp::Reverse(trade.expected_value(strategy.ev_config, revert_probability_bps))
+        });
+
+        let mut claimed_paths = Vec::new();
+        for (strategy, trade) in ranked_trades {
+            if !tx_pool.is_opportunity_still_valid(&trade) {
+                println!("Skipping stale opportunity for strategy {}: victim trade no longer pending or past its deadline", strategy.name);
+                continue;
+            }
+            if tx_pool.path_conflicts_with_in_flight(&trade.path) {
+                println!(
+                    "Preempted opportunity for strategy {}: pools already claimed by a higher-value opportunity this iteration",
+                    strategy.name
+                );
+                continue;
+            }
+            if !tx_pool.submission_allowed() {
+                println!(
+                    "Skipping opportunity for strategy {}: submission throttle cap reached",
+                    strategy.name
+                );
+                continue;
+            }
+
+            match trade
+                .sanity_check(&tx_pool.protocols, *QUOTE_SANITY_MAX_DIVERGENCE_BPS)
+                .await
+            {
+                Ok(true) => {}
+                Ok(false) => {
+                    println!(
+                        "Skipping opportunity for strategy {}: on-chain getAmountsOut diverged from our modeled output by more than {} bps, possible pricing bug",
+                        strategy.name, *QUOTE_SANITY_MAX_DIVERGENCE_BPS
+                    );
+                    continue;
+                }
+                Err(error) => {
+                    println!(
+                        "Skipping opportunity for strategy {}: quote sanity check failed: {}",
+                        strategy.name, error
+                    );
+                    continue;
+                }
+            }
+
+            tx_pool.reserve_in_flight_pools(&trade.path).unwrap();
+            tx_pool.note_attempted(&trade.path);
+            tx_pool.note_submission();
+
+            if let Some(block_number) = cursor.last_processed_block {
+                let fingerprint = trade.path.fingerprint();
+                let replay_guard_pool = pool.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = stats::record_opportunity_submission(
+                        replay_guard_pool,
+                        fingerprint,
+                        block_number,
+                    )
                     .await
-                    .unwrap();
+                    {
+                        println!(
+                            "Failed to persist opportunity fingerprint [{}]: {}",
+                            code_of(&error),
+                            error
+                        );
+                    }
+                });
+            }
+
+            if let Ok(pool_addresses) =
+                trade.path.pool_addresses(&tx_pool.protocols, &tx_pool.custom_pairs)
+            {
+                let stats_pool = pool.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = stats::record_routed(stats_pool, pool_addresses).await {
+                        println!(
+                            "Failed to record pair routing stats [{}]: {}",
+                            code_of(&error),
+                            error
+                        );
+                    }
+                });
             }
+
+            let new_balance = execute_trade(
+                trade.clone(),
+                client.clone(),
+                &tx_pool.protocols,
+                &tx_pool.custom_pairs,
+                &arbitrage_contract,
+                arb_contract_version,
+                &main_wallet,
+                &strategy.wallets,
+                &wallet_strategies,
+                chain_id,
+                chain_profile,
+                pool.clone(),
+                strategy.target,
+                balances[&strategy.target],
+                tx_pool.submission_registry.clone(),
+            )
+            .await
+            .unwrap();
+
+            tx_pool.release_submission();
+            claimed_paths.push(trade.path);
+
+            balances.insert(strategy.target, new_balance);
+        }
+
+        // Pools claimed above stay reserved until every ranked candidate has
+        // been considered, so a lower-value one sharing them is preempted.
+        for path in &claimed_paths {
+            tx_pool.release_in_flight_pools(path).unwrap();
+        }
+    }
+}
+
+async fn token_balances(
+    endpoints: &EndpointPool,
+    arbitrage_contract: &ArbContract<WSClient>,
+    strategies: &[Strategy],
+) -> HashMap<Address, U256> {
+    let mut balances = HashMap::with_capacity(strategies.len());
+    for strategy in strategies {
+        if balances.contains_key(&strategy.target) {
+            continue;
+        }
+        let token: erc20<WSClient> =
+            erc20::new(strategy.target, Arc::new(endpoints.next_read_client()));
+        let balance = token
+            .balance_of(arbitrage_contract.address())
+            .call()
+            .await
+            .unwrap();
+        balances.insert(strategy.target, balance);
+    }
+    balances
+}
+
+/// Refreshes `balances` against the chain, correcting any drift the
+/// receipt-log-based tracking in `execute_trade` may have accumulated (e.g.
+/// a fee-on-transfer token whose `Transfer` event doesn't carry the amount
+/// actually credited). A token whose `balance_of` call fails keeps its last
+/// known balance rather than crashing the loop; it will simply be retried
+/// at the next reconcile.
+async fn reconcile_balances(
+    endpoints: &EndpointPool,
+    arbitrage_contract: &ArbContract<WSClient>,
+    balances: &mut HashMap<Address, U256>,
+) {
+    for (token, balance) in balances.iter_mut() {
+        let contract: erc20<WSClient> = erc20::new(*token, Arc::new(endpoints.next_read_client()));
+        match contract.balance_of(arbitrage_contract.address()).call().await {
+            Ok(fresh) => *balance = fresh,
+            Err(error) => println!("Failed to reconcile balance for token {:?}: {}", token, error),
         }
     }
 }
@@ -148,69 +910,251 @@ async fn reload_protocols_and_pairs(
     HashMap<Address, Protocol>,
     FxHashMap<(Address, Address), Pair>,
 )> {
-    let protocols = generate_protocols(client.clone(), PROTOCOLS_PATH, pool.clone())
+    let protocols = generate_protocols(client.clone(), PROTOCOLS_PATH.as_str(), pool.clone())
         .await
         .unwrap();
-    let pairs_future = tokio::spawn(generate_custom_pairs(CUSTOM_PAIRS, client.clone()));
-    let protocol_future =
-        tokio::spawn(async move { update_all_pairs(protocols, client.clone()).await });
+    let pairs_future = tokio::spawn(generate_custom_pairs(CUSTOM_PAIRS.as_str(), client.clone()));
+    let protocol_future = tokio::spawn({
+        let client = client.clone();
+        async move { update_all_pairs(protocols, client).await }
+    });
 
     let (protocols, pairs) = tokio::join!(protocol_future, pairs_future);
+    let mut pairs = pairs??;
 
-    Ok((protocols??, pairs??))
+    let arbitrage_contract: ArbContract<WSClient> = ArbContract::new(
+        Address::from_str(ARBITRAGE_CONTRACT.as_str()).unwrap(),
+        Arc::new(client.clone()),
+    );
+    let wrapped_native = arbitrage_contract.weth().call().await?;
+    let wrap_pair = Pair::new_native_wrap(client, *NATIVE_TOKEN_ADDRESS, wrapped_native);
+    pairs.insert(wrap_pair.get_tokens(), wrap_pair);
+
+    Ok((protocols??, pairs))
 }
 
-fn estimate_gas(gas: Gas) -> U256 {
+/// Estimates the expected gas cost of submitting a `num_hops`-hop arbitrage
+/// on `chain_profile`'s chain: the full cost of one successful attempt plus
+/// a cheaper reverted cost for each of `TRANSACTION_ATTEMPTS - 1` retries.
+/// `GAS_ESTIMATE` used to be a single flat figure regardless of chain or
+/// path length; `chain_profile` now supplies both, since 500k gas and an
+/// 8x success/fail ratio are wildly wrong on some L2s and for long paths.
+fn estimate_gas(gas: Gas, chain_profile: ChainProfile, num_hops: usize) -> U256 {
     let gas_price = match gas {
         Gas::Legacy(price) => price,
         Gas::London(max_fee, _max_priority_fee) => max_fee,
     };
-    let gas_estimate = U256::from(GAS_ESTIMATE);
+    let extra_hops = num_hops.saturating_sub(1) as u32;
+    let gas_estimate = U256::from(chain_profile.base_gas_estimate)
+        + U256::from(chain_profile.gas_per_hop).saturating_mul(U256::from(extra_hops));
     let gas_for_success = gas_estimate.saturating_mul(gas_price);
-    let gas_for_fail = gas_estimate.div(8).saturating_mul(gas_price);
+    let gas_for_fail = gas_estimate
+        .div(chain_profile.fail_gas_divisor.max(1))
+        .saturating_mul(gas_price);
     gas_for_success.saturating_add(gas_for_fail.saturating_mul((*TRANSACTION_ATTEMPTS - 1).into()))
 }
 
-async fn get_profitable_arbitrage<'a>(
+/// Tracks how long it's been since the last new block header and a rolling
+/// estimate of the chain's average block interval, so the main loop can tell
+/// when the next block is imminent and avoid starting a search/submission
+/// pass likely to finish and land after reserves have already moved.
+struct BlockTiming {
+    last_block_at: Instant,
+    average_interval: Duration,
+}
+
+impl BlockTiming {
+    fn new() -> Self {
+        Self {
+            last_block_at: Instant::now(),
+            average_interval: Duration::from_secs(3),
+        }
+    }
+
+    /// Folds a freshly observed inter-block gap into the rolling average
+    /// with an exponential moving average, so a one-off stall (an RPC
+    /// hiccup, a quiet chain) doesn't permanently skew the estimate the way
+    /// a simple running mean would.
+    fn note_block(&mut self) {
+        let observed = self.last_block_at.elapsed();
+        self.last_block_at = Instant::now();
+        let blended_millis =
+            (self.average_interval.as_millis() as u64 * 3 + observed.as_millis() as u64) / 4;
+        self.average_interval = Duration::from_millis(blended_millis.max(1));
+    }
+
+    /// Whether the next block is expected within `margin`, i.e. close
+    /// enough that a search/submission pass started now would likely still
+    /// be in flight (or only just landing) once reserves move underneath
+    /// it.
+    fn next_block_imminent(&self, margin: Duration) -> bool {
+        self.last_block_at.elapsed() + margin >= self.average_interval
+    }
+}
+
+/// Refreshes reserves and retires trades that mined in `block`, persisting
+/// how far we've gotten so a restart can tell how stale local state is.
+/// Shared by the main loop's own non-blocking block check and by
+/// `get_profitable_arbitrages`, which can also receive a block directly when
+/// one arrives mid-search (see `TxPool::get_arbitrages`) and must not let it
+/// go unprocessed just because it didn't come through the usual check.
+async fn process_new_block(
+    tx_pool: &mut TxPool<'_>,
+    cursor: &mut BlockCursor,
+    block_timing: &mut BlockTiming,
+    block: Block<H256>,
+) {
+    block_timing.note_block();
+    tx_pool.get_all_reserves().await.unwrap();
+    let tx_hashes = block.transactions;
+    tx_pool.remove_done_trades(tx_hashes).await.unwrap();
+    tx_pool.mark_unsimulated();
+    tx_pool.reset_block_submissions();
+    if let Some(block_number) = block.number {
+        cursor.last_processed_block = Some(block_number.as_u64());
+        cursor.last_reserve_sync_block = Some(block_number.as_u64());
+        if let Err(error) = save_cursor(CURSOR_PATH.as_str(), *cursor).await {
+            println!("Failed to persist block cursor: {}", error);
+        }
+    }
+    println!("Got new reserves");
+}
+
+/// Searches on behalf of every strategy in one pass (see
+/// `TxPool::get_arbitrages`) and scores each strategy's candidates against
+/// its own expected-value thresholds, returning one opportunity (if any) per
+/// strategy in the same order as `strategies`. Ties within
+/// `EV_TIE_EPSILON_BPS` of the best expected value are broken by
+/// `select_tied_arbitrage` rather than by whichever candidate happened to be
+/// found first, so the choice doesn't jitter between equally-good candidates
+/// from one iteration to the next.
+///
+/// If a new block interrupts the search, it's processed via
+/// `process_new_block` before this function returns, same as if the main
+/// loop's own block check had caught it — the only difference is timing.
+async fn get_profitable_arbitrages<'a>(
     tx_pool: &mut TxPool<'a>,
-    input_amount: U256,
+    strategies: &[Strategy],
+    inputs: &[U256],
+    penalty_factors: &FxHashMap<Address, u32>,
+    block_subscription: &mut SubscriptionStream<'_, Ws, Block<H256>>,
+    cursor: &mut BlockCursor,
+    block_timing: &mut BlockTiming,
+) -> Vec<Option<PossibleArbitrage>> {
+    let (arbitrages, interrupting_block) = tx_pool
+        .get_arbitrages(strategies, inputs, block_subscription)
+        .await
+        .unwrap();
+    if let Some(block) = interrupting_block {
+        process_new_block(tx_pool, cursor, block_timing, block).await;
+    }
+    let revert_probability_bps = REVERT_STATS.revert_probability_bps();
+
+    arbitrages
+        .into_iter()
+        .zip(strategies)
+        .map(|(candidates, strategy)| {
+            let candidates: Vec<PossibleArbitrage> = candidates
+                .into_iter()
+                .filter(|arbitrage| {
+                    arbitrage.expected_value(strategy.ev_config, revert_probability_bps) > 0
+                })
+                .collect();
+            select_tied_arbitrage(
+                candidates,
+                strategy,
+                revert_probability_bps,
+                tx_pool,
+                penalty_factors,
+            )
+        })
+        .collect()
+}
+
+/// Among candidates within `EV_TIE_EPSILON_BPS` of the best expected value,
+/// prefers the shorter path (fewer pools to revert or get sandwiched on),
+/// then the deeper pool (less slippage for the same size), then the pool
+/// we've observed least revert/slippage competition on. Candidates outside
+/// the epsilon window never reach the tie-break and lose on raw expected
+/// value alone.
+fn select_tied_arbitrage(
+    candidates: Vec<PossibleArbitrage>,
+    strategy: &Strategy,
+    revert_probability_bps: u32,
+    tx_pool: &TxPool,
+    penalty_factors: &FxHashMap<Address, u32>,
 ) -> Option<PossibleArbitrage> {
-    let arbitrages = tx_pool.get_arbitrages(input_amount).await.unwrap();
-    let best_arbitrage = arbitrages
+    let best_ev = candidates
+        .iter()
+        .map(|arbitrage| arbitrage.expected_value(strategy.ev_config, revert_probability_bps))
+        .max()?;
+    let epsilon = best_ev
+        .unsigned_abs()
+        .saturating_mul(u128::from(*EV_TIE_EPSILON_BPS))
+        / 10_000;
+    let threshold = best_ev.saturating_sub(epsilon as i128);
+
+    candidates
         .into_iter()
-        .max_by_key(|arbitrage| arbitrage.profit.saturating_sub(arbitrage.gas_in_eth));
-
-    match best_arbitrage {
-        None => None,
-        Some(arbitrage) => {
-            if arbitrage.profit.saturating_sub(arbitrage.gas_in_eth) > 0.into() {
-                Some(arbitrage)
-            } else {
-                None
-            }
-        }
-    }
+        .filter(|arbitrage| {
+            arbitrage.expected_value(strategy.ev_config, revert_probability_bps) >= threshold
+        })
+        .max_by_key(|arbitrage| {
+            let liquidity = arbitrage
+                .path
+                .min_pool_liquidity(&tx_pool.protocols, &tx_pool.custom_pairs)
+                .unwrap_or(0);
+            let competition_penalty: u32 = arbitrage
+                .path
+                .pool_addresses(&tx_pool.protocols, &tx_pool.custom_pairs)
+                .unwrap_or_default()
+                .iter()
+                .map(|address| penalty_factors.get(address).copied().unwrap_or(0))
+                .fold(0u32, u32::saturating_add);
+            (
+                std::cmp::Reverse(arbitrage.path.pair_order.len()),
+                liquidity,
+                std::cmp::Reverse(competition_penalty),
+            )
+        })
 }
 
 async fn ensure_gas_reserves(
-    client: WSClient,
+    endpoints: &EndpointPool,
     main_account: &LocalWallet,
     other_accounts: &[LocalWallet],
     arb_contract: &ArbContract<WSClient>,
 ) -> Result<()> {
-    let current_main_reserve = client.get_balance(main_account.address(), None).await?;
+    let current_main_reserve = RPC_BUDGET
+        .run(
+            arb_contract.address(),
+            endpoints
+                .next_read_client()
+                .get_balance(main_account.address(), None),
+        )
+        .await?;
 
     let low_accounts = futures::stream::iter(other_accounts.iter())
         .filter(|account| async {
-            client.get_balance(account.address(), None).await.unwrap() < *BALANCE_RESERVE
+            let balance = RPC_BUDGET
+                .run(
+                    arb_contract.address(),
+                    endpoints
+                        .next_read_client()
+                        .get_balance(account.address(), None),
+                )
+                .await
+                .unwrap();
+            balance < *BALANCE_RESERVE
         })
         .collect::<Vec<&LocalWallet>>()
         .await;
 
     let top_ups = low_accounts.len() + (current_main_reserve < *BALANCE_RESERVE) as usize;
 
+    let client = endpoints.fastest_client();
     if top_ups > 0 {
-        let gas_price = client.get_gas_price().await?;
+        let gas_price = GAS_PRICE_STRATEGY.quote(client.clone()).await?;
         let amount = BALANCE_RESERVE.saturating_mul(top_ups.into());
         let tx = arb_contract.withdraw_eth(amount).gas_price(gas_price);
         let receipt: TransactionReceipt = tx.send_raw(main_account, client.clone()).await?.unwrap();
@@ -232,6 +1176,102 @@ async fn ensure_gas_reserves(
     Ok(())
 }
 
+/// Checks the contract's native/traded-token inventory against `policy` and,
+/// if it has drifted out of tolerance, converts along the best on-graph path
+/// to pull it back toward the target ratio. Returns whether a conversion was
+/// sent.
+async fn maybe_rebalance(
+    client: WSClient,
+    arb_contract: &ArbContract<WSClient>,
+    arb_contract_version: ArbContractVersion,
+    main_wallet: &LocalWallet,
+    protocols: &HashMap<Address, Protocol>,
+    custom_pairs: &FxHashMap<(Address, Address), Pair>,
+    traded_token: Address,
+    native_token: Address,
+    chain_id: U256,
+    policy: RebalancePolicy,
+) -> Result<bool> {
+    let traded_contract: erc20<WSClient> = erc20::new(traded_token, Arc::new(client.clone()));
+    let traded_balance = traded_contract
+        .balance_of(arb_contract.address())
+        .call()
+        .await?;
+    let native_balance = client.get_balance(arb_contract.address(), None).await?;
+
+    let Some(plan) = plan_rebalance(
+        protocols,
+        custom_pairs,
+        traded_token,
+        native_token,
+        traded_balance,
+        native_balance,
+        policy,
+    )?
+    else {
+        return Ok(false);
+    };
+
+    let pool_path: Vec<(Address, U256)> = plan
+        .path
+        .pair_order
+        .iter()
+        .map(|lookup| {
+            let pair = match protocols.get(&lookup.factory_address) {
+                None => custom_pairs
+                    .get(&lookup.pair_addresses)
+                    .ok_or_else(|| anyhow!("Pair not found in customs")),
+                Some(protocol) => protocol
+                    .pairs
+                    .get(&lookup.pair_addresses)
+                    .ok_or_else(|| anyhow!("Pair not found in protocol")),
+            }
+            .unwrap();
+            (pair.contract.address(), pair.encoded_fee())
+        })
+        .collect();
+    let pool_order: Vec<Address> = pool_path.iter().map(|item| item.0).collect();
+    let fee_order: Vec<U256> = pool_path.iter().map(|item| item.1).collect();
+
+    let deadline = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+        + 120;
+
+    let gas_price = GAS_PRICE_STRATEGY.quote(client.clone()).await?;
+    let mut call = build_attempt_arbitrage_call(
+        arb_contract_version,
+        arb_contract,
+        plan.amount_in,
+        plan.min_output,
+        plan.path.token_order.clone(),
+        pool_order,
+        fee_order,
+        U256::from(deadline),
+    )
+    .gas_price(gas_price)
+    .gas(GAS_ESTIMATE);
+    let nonce = client
+        .get_transaction_count(main_wallet.address(), None)
+        .await?;
+    call.tx.set_nonce(nonce);
+    call.tx.set_chain_id(chain_id.as_u64());
+    let signature = main_wallet.sign_transaction(&call.tx).await?;
+    let tx = call.tx.rlp_signed(&signature);
+    client.send_raw_transaction(tx).await?.await?;
+
+    println!(
+        "Rebalanced {} of {:#x} into {:#x} (min output {})",
+        plan.amount_in,
+        plan.path.token_order.first().copied().unwrap_or(traded_token),
+        plan.path.token_order.last().copied().unwrap_or(native_token),
+        plan.min_output
+    );
+
+    Ok(true)
+}
+
 async fn pay(
     receiver: Address,
     amount: U256,
@@ -262,7 +1302,8 @@ trait SendRaw {
         self,
         signer: &LocalWallet,
         client: WSClient,
-    ) -> Result<Option<TransactionReceipt>>;
+        submission_registry: Arc<SubmissionRegistry>,
+    ) -> Result<(H256, Option<TransactionReceipt>)>;
 }
 
 #[async_trait]
@@ -271,14 +1312,43 @@ impl<D: Detokenize + Send + Sync, C: Sync + Send> SendRaw for ContractCall<C, D>
         mut self,
         signer: &LocalWallet,
         client: WSClient,
-    ) -> Result<Option<TransactionReceipt>> {
+        submission_registry: Arc<SubmissionRegistry>,
+    ) -> Result<(H256, Option<TransactionReceipt>)> {
         let nonce = client.get_transaction_count(signer.address(), None).await?;
         self.tx.set_nonce(nonce);
         let signature = signer.sign_transaction(&self.tx).await?;
         let tx = self.tx.rlp_signed(&signature);
 
-        let pending = client.send_raw_transaction(tx).await?.await?;
-        Ok(pending)
+        let pending = client.send_raw_transaction(tx).await?;
+        let tx_hash = *pending;
+        submission_registry.register(tx_hash, signer.address());
+        let receipt = match pending.await {
+            Ok(receipt) => receipt,
+            Err(error) => {
+                submission_registry.settle(tx_hash, SubmissionStatus::Cancelled);
+                return Err(error.into());
+            }
+        };
+        Ok((tx_hash, receipt))
+    }
+}
+
+fn apply_gas<D>(
+    mut call: ContractCall<WSClient, D>,
+    gas: Gas,
+) -> Result<ContractCall<WSClient, D>> {
+    match gas {
+        Gas::Legacy(price) => Ok(call.legacy().gas_price(price)),
+        Gas::London(max_fee, max_priority_fee) => match call.tx {
+            TypedTransaction::Eip1559(tx) => {
+                call.tx = TypedTransaction::Eip1559(
+                    tx.max_fee_per_gas(max_fee)
+                        .max_priority_fee_per_gas(max_priority_fee),
+                );
+                Ok(call)
+            }
+            _ => bail!("Typed transaction should only be EIP1559"),
+        },
     }
 }
 
@@ -288,12 +1358,23 @@ async fn execute_trade(
     protocols: &HashMap<Address, Protocol>,
     custom_pairs: &FxHashMap<(Address, Address), Pair>,
     arb_contract: &ArbContract<WSClient>,
+    arb_contract_version: ArbContractVersion,
+    main_wallet: &LocalWallet,
     accounts: &[LocalWallet],
+    wallet_strategies: &HashMap<Address, WalletStrategy>,
     chain_id: U256,
-) -> Result<()> {
+    chain_profile: ChainProfile,
+    stats_pool: Arc<Pool>,
+    target_token: Address,
+    target_balance: U256,
+    submission_registry: Arc<SubmissionRegistry>,
+) -> Result<U256> {
+    let profit = arb.profit;
+    let quoted_output = arb.output;
     let balance_to_spend = arb.input;
     let min_output = balance_to_spend.saturating_add(arb.gas_in_eth);
-    let pool_path: Vec<(Address, u32)> = arb
+    let opportunity_path = arb.path.token_order.clone();
+    let pool_path: Vec<(Address, U256)> = arb
         .path
         .pair_order
         .iter()
@@ -308,12 +1389,13 @@ async fn execute_trade(
                     .ok_or_else(|| anyhow!("Pair not found in protocol")),
             }
             .unwrap();
-            (pair.contract.address(), pair.fee)
+            (pair.contract.address(), pair.encoded_fee())
         })
         .collect();
 
     let pool_order: Vec<Address> = pool_path.iter().map(|item| item.0).collect();
-    let fee_order: Vec<U256> = pool_path.iter().map(|item| U256::from(item.1)).collect();
+    let fee_order: Vec<U256> = pool_path.iter().map(|item| item.1).collect();
+    let routed_pools = pool_order.clone();
 
     let deadline = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -322,7 +1404,9 @@ async fn execute_trade(
         + 120;
 
     let mut futures = Vec::with_capacity(accounts.len());
-    let mut call = arb_contract.attempt_arbitrage(
+    let base_call = build_attempt_arbitrage_call(
+        arb_contract_version,
+        arb_contract,
         balance_to_spend,
         min_output,
         arb.path.token_order,
@@ -331,32 +1415,212 @@ async fn execute_trade(
         U256::from(deadline),
     );
 
-    let gassed_call = match arb.gas {
-        Gas::Legacy(price) => call.legacy().gas_price(price),
-        Gas::London(max_fee, max_priority_fee) => match call.tx {
-            TypedTransaction::Eip1559(tx) => {
-                call.tx = TypedTransaction::Eip1559(
-                    tx.max_fee_per_gas(max_fee)
-                        .max_priority_fee_per_gas(max_priority_fee),
+    // A true atomic bundle simulation (victim tx + ours, via `eth_callBundle`
+    // against a relay or a local EVM fork) isn't available here: we submit
+    // plain transactions rather than relay bundles. This plain `eth_call`
+    // against the latest state is the cheap approximation of it - it still
+    // catches a stale or wrong quote before we broadcast and pay gas for a
+    // guaranteed revert.
+    let simulated_amounts: Vec<U256> = match base_call.call().await {
+        Ok(amounts) => amounts,
+        Err(error) => {
+            println!(
+                "Bundle simulation reverted before submission, aborting: {}",
+                error
+            );
+            return Ok(target_balance);
+        }
+    };
+    let simulated_output = *simulated_amounts.last().unwrap_or(&U256::zero());
+    if simulated_output < min_output {
+        println!(
+            "Bundle simulation output {} below required minimum {}, aborting before submission",
+            simulated_output, min_output
+        );
+        return Ok(target_balance);
+    }
+
+    {
+        let slippage_pools = routed_pools.clone();
+        let slippage_pool = stats_pool.clone();
+        tokio::spawn(async move {
+            if let Err(error) =
+                stats::record_slippage(slippage_pool, slippage_pools, quoted_output, simulated_output)
+                    .await
+            {
+                println!(
+                    "Failed to record pair slippage stats [{}]: {}",
+                    code_of(&error),
+                    error
                 );
-                call
-            }
-            _ => {
-                bail!("Typed transaction should only be EIP1559")
             }
-        },
-    };
-    let mut call = gassed_call.gas(GAS_ESTIMATE);
-    call.tx.set_chain_id(chain_id.as_u64());
+        });
+    }
 
     for account in accounts {
-        let fut = call.clone().send_raw(account, client.clone());
-        futures.push(fut);
+        let strategy = wallet_strategies
+            .get(&account.address())
+            .copied()
+            .unwrap_or_default();
+        let gas = scale_gas(arb.gas, strategy, chain_profile);
+        let mut account_call = apply_gas(base_call.clone(), gas)
+            .code(ErrorCode::Execution)?
+            .gas(GAS_ESTIMATE);
+        account_call.tx.set_chain_id(chain_id.as_u64());
+
+        let fut = account_call.send_raw(account, client.clone(), submission_registry.clone());
+        futures.push(fut.map(move |result| (account.address(), result)));
     }
 
-    let receipts = join_all(futures).await;
+    let outcomes = join_all(futures).await;
 
-    dbg!(receipts);
+    let mut mined = false;
+    let mut mined_receipt: Option<TransactionReceipt> = None;
+    let mut failed_attempt_charges: Vec<(Address, U256)> = Vec::new();
+    let mut any_failed = false;
+    for (wallet, outcome) in outcomes {
+        match outcome {
+            Ok((tx_hash, receipt)) => {
+                let status = match &receipt {
+                    Some(receipt) if receipt.status.unwrap_or_default().as_u64() == 1 => {
+                        mined = true;
+                        mined_receipt = Some(receipt.clone());
+                        REVERT_STATS.record(false);
+                        SubmissionStatus::Mined
+                    }
+                    Some(receipt) => {
+                        let gas_cost = receipt
+                            .gas_used
+                            .unwrap_or_default()
+                            .saturating_mul(receipt.effective_gas_price.unwrap_or_default());
+                        failed_attempt_charges.push((wallet, gas_cost));
+                        REVERT_STATS.record(true);
+                        any_failed = true;
+                        SubmissionStatus::Failed
+                    }
+                    None => SubmissionStatus::Cancelled,
+                };
+                submission_registry.settle(tx_hash, status);
+            }
+            Err(error) => println!("Submission failed to send: {}", error),
+        }
+    }
+    if any_failed {
+        let revert_pools = routed_pools.clone();
+        let revert_pool = stats_pool.clone();
+        tokio::spawn(async move {
+            if let Err(error) = stats::record_reverts(revert_pool, revert_pools).await {
+                println!(
+                    "Failed to record pair revert stats [{}]: {}",
+                    code_of(&error),
+                    error
+                );
+            }
+        });
+    }
 
-    Ok(())
+    if !failed_attempt_charges.is_empty() {
+        println!(
+            "Reverted attempt(s) for opportunity {:?}: {} wallet(s) burned gas",
+            opportunity_path,
+            failed_attempt_charges.len()
+        );
+        let gas_burn_pool = stats_pool.clone();
+        tokio::spawn(async move {
+            if let Err(error) =
+                stats::record_failed_attempt_gas(gas_burn_pool, failed_attempt_charges).await
+            {
+                println!(
+                    "Failed to record wallet gas burn stats [{}]: {}",
+                    code_of(&error),
+                    error
+                );
+            }
+        });
+    }
+
+    let mut target_balance = target_balance;
+    if let Some(receipt) = &mined_receipt {
+        let token_contract: erc20<WSClient> = erc20::new(target_token, Arc::new(client.clone()));
+        match transfer_delta_from_receipt(&token_contract, arb_contract.address(), receipt) {
+            Ok(delta) => target_balance = apply_signed_delta(target_balance, delta),
+            Err(error) => println!(
+                "Failed to derive balance delta from receipt, balance estimate may drift until next reconcile: {}",
+                error
+            ),
+        }
+    }
+
+    if mined {
+        let bribe = compute_bribe(profit, *BUILDER_BRIBE_BPS);
+        let mined_block = mined_receipt
+            .as_ref()
+            .and_then(|receipt| receipt.block_hash)
+            .ok_or_else(|| anyhow!("Mined trade's receipt has no block hash"))
+            .code(ErrorCode::Execution)?;
+        tip_builder(client, arb_contract, main_wallet, bribe, mined_block)
+            .await
+            .code(ErrorCode::Execution)?;
+    }
+
+    Ok(target_balance)
+}
+
+/// Reads the net change in `holder`'s balance of `token_contract` implied by
+/// `receipt`'s `Transfer` logs, so a trade's effect on our own balance can be
+/// read straight out of the receipt we already fetched instead of an extra
+/// `balance_of` round-trip in the hot loop.
+fn transfer_delta_from_receipt(
+    token_contract: &erc20<WSClient>,
+    holder: Address,
+    receipt: &TransactionReceipt,
+) -> Result<i128> {
+    let transfer_event = token_contract.abi().event("Transfer")?;
+    let mut delta: i128 = 0;
+    for log in &receipt.logs {
+        if log.address != token_contract.address() {
+            continue;
+        }
+        let raw_log = ethers::abi::RawLog {
+            topics: log.topics.clone(),
+            data: log.data.to_vec(),
+        };
+        let Ok(parsed) = transfer_event.parse_log(raw_log) else {
+            continue;
+        };
+        let from = parsed
+            .params
+            .iter()
+            .find(|param| param.name == "from")
+            .and_then(|param| param.value.clone().into_address());
+        let to = parsed
+            .params
+            .iter()
+            .find(|param| param.name == "to")
+            .and_then(|param| param.value.clone().into_address());
+        let value = parsed
+            .params
+            .iter()
+            .find(|param| param.name == "value")
+            .and_then(|param| param.value.clone().into_uint());
+        let (Some(from), Some(to), Some(value)) = (from, to, value) else {
+            continue;
+        };
+        let value = i128::try_from(value.as_u128()).unwrap_or(i128::MAX);
+        if to == holder {
+            delta = delta.saturating_add(value);
+        }
+        if from == holder {
+            delta = delta.saturating_sub(value);
+        }
+    }
+    Ok(delta)
+}
+
+fn apply_signed_delta(balance: U256, delta: i128) -> U256 {
+    if delta >= 0 {
+        balance.saturating_add(U256::from(delta.unsigned_abs()))
+    } else {
+        balance.saturating_sub(U256::from(delta.unsigned_abs()))
+    }
 }
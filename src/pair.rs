@@ -2,10 +2,10 @@ use std::fmt::Debug;
 use std::panic::panic_any;
 use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ethers::prelude::Address;
 use ethers::types::U256;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use thiserror::Error;
 
 use crate::v2protocol::{SwapPool, WSClient};
@@ -19,6 +19,55 @@ pub struct Pair {
     pub reserve1: u128,
     pub fee: u32,
     pub factory_address: Address,
+    decimals0: u8,
+    decimals1: u8,
+    kind: PoolKind,
+}
+
+/// Which swap curve a pair follows. Most pairs are plain constant-product
+/// AMMs; `Pmm` covers DODO-style proactive market maker pools, which quote
+/// around an external oracle price rather than their own reserve ratio;
+/// `NativeWrap` is the synthetic 1:1 edge between the chain's native asset
+/// and its wrapped ERC-20 representation.
+#[derive(Debug, Clone)]
+pub enum PoolKind {
+    ConstantProduct,
+    Pmm(PmmParams),
+    NativeWrap,
+}
+
+impl PoolKind {
+    /// Small integer discriminant for this pool's swap curve, packed into
+    /// the high byte of `Pair::encoded_fee` so a path mixing protocols can
+    /// tell `ArbContract` which hop needs which handling without changing
+    /// the flat `fees: uint256[]` parameter `attempt_arbitrage` already
+    /// takes. `3` and above are reserved for kinds we don't route yet
+    /// (e.g. V3).
+    const fn execution_tag(&self) -> u8 {
+        match self {
+            Self::ConstantProduct => 0,
+            Self::Pmm(_) => 1,
+            Self::NativeWrap => 2,
+        }
+    }
+}
+
+/// Parameters for a PMM pool's quote curve: an external oracle `price` the
+/// pool quotes around, and a `depth` controlling how much slippage a trade
+/// incurs moving away from that price. This is a simplified stand-in for
+/// DODO's actual curve (which also tracks inventory skew); it's enough to
+/// route through PMM liquidity without pretending to replicate DODO exactly.
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+pub struct PmmParams {
+    /// Price of one unit of `token0` in `token1`, scaled to 1e18.
+    pub external_price: U256,
+    /// Notional amount of the input token the curve absorbs before slippage
+    /// becomes significant.
+    pub depth: U256,
+}
+
+fn default_decimals() -> u8 {
+    18
 }
 
 #[derive(serde::Deserialize)]
@@ -28,7 +77,28 @@ pub struct JsonPair {
     token1: Address,
     reserve0: u128,
     reserve1: u128,
-    fee: u32,
+    /// Falls back to the enclosing group's `default_fee` when omitted, so a
+    /// group of otherwise-identical pairs doesn't need to repeat it per pair.
+    #[serde(default)]
+    fee: Option<u32>,
+    #[serde(default = "default_decimals")]
+    decimals0: u8,
+    #[serde(default = "default_decimals")]
+    decimals1: u8,
+    /// Present for DODO/PMM-style pools; absent pairs are treated as plain
+    /// constant-product AMMs.
+    #[serde(default)]
+    pmm: Option<PmmParams>,
+}
+
+/// A named collection of custom pairs sharing a default fee, e.g. pairs
+/// belonging to the same off-registry protocol. Lets `custom_pairs.json`
+/// group pairs instead of repeating the same fee on every entry.
+#[derive(serde::Deserialize)]
+pub struct JsonPairGroup {
+    pub name: String,
+    default_fee: u32,
+    pairs: Vec<JsonPair>,
 }
 
 #[derive(Error, Debug)]
@@ -52,6 +122,8 @@ impl Pair {
         token1: Address,
         fee: u32,
         factory_address: Address,
+        decimals0: u8,
+        decimals1: u8,
     ) -> Self {
         Self {
             contract,
@@ -61,27 +133,94 @@ impl Pair {
             reserve1: 0,
             fee,
             factory_address,
+            decimals0,
+            decimals1,
+            kind: PoolKind::ConstantProduct,
         }
     }
 
-    pub fn from_jsonpair(json: JsonPair, client: WSClient) -> Self {
+    pub fn from_jsonpair(json: JsonPair, client: WSClient, default_fee: u32) -> Self {
         let contract = json.address.generate_pool_contract(client);
+        let kind = json.pmm.map_or(PoolKind::ConstantProduct, PoolKind::Pmm);
         Self {
             contract,
             token0: json.token0,
             token1: json.token1,
             reserve0: json.reserve0,
             reserve1: json.reserve1,
-            fee: json.fee,
+            fee: json.fee.unwrap_or(default_fee),
+            factory_address: Address::zero(),
+            decimals0: json.decimals0,
+            decimals1: json.decimals1,
+            kind,
+        }
+    }
+
+    /// Builds the synthetic 1:1 edge between the chain's native asset
+    /// (`native`) and its wrapped ERC-20 representation (`wrapped`), so the
+    /// pathfinder can route through the wrap/unwrap step `ArbContract`
+    /// performs internally. There's no real pool contract backing this
+    /// edge; `wrapped`'s own address doubles as the "pool" address, since
+    /// `ArbContract` already knows its own WETH address and can recognize
+    /// it in the pool path as a wrap/unwrap rather than a swap.
+    pub fn new_native_wrap(client: WSClient, native: Address, wrapped: Address) -> Self {
+        let contract = SwapPool::new(wrapped, client.into());
+        Self {
+            contract,
+            token0: native,
+            token1: wrapped,
+            reserve0: 0,
+            reserve1: 0,
+            fee: 0,
             factory_address: Address::zero(),
+            decimals0: 18,
+            decimals1: 18,
+            kind: PoolKind::NativeWrap,
         }
     }
 
+    /// Scales `amount` of `token` to a normalized 1e18 basis so path weights
+    /// built from tokens with different decimals remain comparable; low
+    /// decimal tokens (USDC/WBTC) would otherwise collapse to tiny integers
+    /// the search can't distinguish between.
+    pub fn normalize_to_1e18(&self, token: Address, amount: U256) -> U256 {
+        let decimals = if token == self.token0 {
+            self.decimals0
+        } else {
+            self.decimals1
+        };
+        scale_to_1e18(amount, decimals)
+    }
+
     pub const fn get_tokens(&self) -> (Address, Address) {
         (self.token0, self.token1)
     }
 
+    /// `fee` (basis points, always well under a byte) with `kind`'s
+    /// execution tag packed into the top byte, so a per-hop pool type
+    /// survives being passed through `attempt_arbitrage`'s `fees: uint256[]`
+    /// without the on-chain ABI needing a dedicated field for it.
+    pub fn encoded_fee(&self) -> U256 {
+        U256::from(self.fee) | (U256::from(self.kind.execution_tag()) << 248)
+    }
+
+    pub fn is_routing_blacklisted(&self, blacklist: &FxHashSet<(Address, Address)>) -> bool {
+        blacklist.contains(&sorted_tokens(self.token0, self.token1))
+    }
+
     pub fn get_amount_out(&self, input: Address, amount_in: U256) -> Result<U256, ArbitrageError> {
+        match &self.kind {
+            PoolKind::ConstantProduct => self.get_amount_out_constant_product(input, amount_in),
+            PoolKind::Pmm(params) => self.get_amount_out_pmm(input, amount_in, *params),
+            PoolKind::NativeWrap => self.wrap_unwrap_amount(input, amount_in),
+        }
+    }
+
+    fn get_amount_out_constant_product(
+        &self,
+        input: Address,
+        amount_in: U256,
+    ) -> Result<U256, ArbitrageError> {
         let reserves = self.get_ordered_reserves(input)?;
         if reserves.input == 0.into() || reserves.output == 0.into() {
             return Err(ArbitrageError::NoLiquidity);
@@ -111,7 +250,55 @@ impl Pair {
         Ok(output)
     }
 
+    /// PMM quote: `amount_in` priced at `params.external_price`, then
+    /// discounted by `params.depth / (params.depth + amount_in)` so larger
+    /// trades get progressively worse pricing as they move away from the
+    /// oracle price.
+    fn get_amount_out_pmm(
+        &self,
+        input: Address,
+        amount_in: U256,
+        params: PmmParams,
+    ) -> Result<U256, ArbitrageError> {
+        let (rate_num, rate_denom) = pmm_rate(self.token0, self.token1, input, params)?;
+        let base_out = amount_in
+            .checked_mul(rate_num)
+            .ok_or(ArbitrageError::MathOverflow)?
+            .checked_div(rate_denom)
+            .ok_or(ArbitrageError::DivideByZero)?;
+        let denominator = params
+            .depth
+            .checked_add(amount_in)
+            .ok_or(ArbitrageError::MathOverflow)?;
+        base_out
+            .checked_mul(params.depth)
+            .ok_or(ArbitrageError::MathOverflow)?
+            .checked_div(denominator)
+            .ok_or(ArbitrageError::DivideByZero)
+    }
+
     pub fn get_amount_in(&self, input: Address, amount_out: U256) -> Result<U256, ArbitrageError> {
+        match &self.kind {
+            PoolKind::ConstantProduct => self.get_amount_in_constant_product(input, amount_out),
+            PoolKind::Pmm(params) => self.get_amount_in_pmm(input, amount_out, *params),
+            PoolKind::NativeWrap => self.wrap_unwrap_amount(input, amount_out),
+        }
+    }
+
+    /// Wrapping/unwrapping is a plain 1:1 conversion in either direction.
+    fn wrap_unwrap_amount(&self, input: Address, amount: U256) -> Result<U256, ArbitrageError> {
+        if input == self.token0 || input == self.token1 {
+            Ok(amount)
+        } else {
+            Err(ArbitrageError::TokenNotInPair)
+        }
+    }
+
+    fn get_amount_in_constant_product(
+        &self,
+        input: Address,
+        amount_out: U256,
+    ) -> Result<U256, ArbitrageError> {
         let reserves = self.get_ordered_reserves(input)?;
         if reserves.input == 0.into() || reserves.output == 0.into() {
             return Err(ArbitrageError::NoLiquidity);
@@ -136,6 +323,32 @@ impl Pair {
         Ok(division.saturating_add(1.into()))
     }
 
+    /// Inverse of `get_amount_out_pmm`: solves the same depth-discounted
+    /// quote for the input needed to produce `amount_out`.
+    fn get_amount_in_pmm(
+        &self,
+        input: Address,
+        amount_out: U256,
+        params: PmmParams,
+    ) -> Result<U256, ArbitrageError> {
+        let (rate_num, rate_denom) = pmm_rate(self.token0, self.token1, input, params)?;
+        let numerator = amount_out
+            .checked_mul(params.depth)
+            .ok_or(ArbitrageError::MathOverflow)?
+            .checked_mul(rate_denom)
+            .ok_or(ArbitrageError::MathOverflow)?;
+        let lhs = rate_num
+            .checked_mul(params.depth)
+            .ok_or(ArbitrageError::MathOverflow)?;
+        let rhs = amount_out
+            .checked_mul(rate_denom)
+            .ok_or(ArbitrageError::MathOverflow)?;
+        let denominator = lhs.checked_sub(rhs).ok_or(ArbitrageError::MathUnderflow)?;
+        numerator
+            .checked_div(denominator)
+            .ok_or(ArbitrageError::DivideByZero)
+    }
+
     fn get_ordered_reserves(&self, input: Address) -> Result<OrderedReserves, ArbitrageError> {
         if input == self.token0 {
             Ok(OrderedReserves::new(self.reserve0, self.reserve1))
@@ -185,6 +398,35 @@ impl PartialPair {
     }
 }
 
+/// The (numerator, denominator) such that `output = input * numerator /
+/// denominator` at a PMM pool's oracle price, oriented by which token is
+/// being sold in.
+fn pmm_rate(
+    token0: Address,
+    token1: Address,
+    input: Address,
+    params: PmmParams,
+) -> Result<(U256, U256), ArbitrageError> {
+    let one_e18 = U256::from(10).pow(18.into());
+    if input == token0 {
+        Ok((params.external_price, one_e18))
+    } else if input == token1 {
+        Ok((one_e18, params.external_price))
+    } else {
+        Err(ArbitrageError::TokenNotInPair)
+    }
+}
+
+/// Scales `amount`, expressed with `decimals` decimal places, onto a
+/// normalized 1e18 basis.
+pub fn scale_to_1e18(amount: U256, decimals: u8) -> U256 {
+    match 18u8.checked_sub(decimals) {
+        Some(0) => amount,
+        Some(shift) => amount.saturating_mul(U256::from(10).pow(shift.into())),
+        None => amount / U256::from(10).pow((decimals - 18).into()),
+    }
+}
+
 struct OrderedReserves {
     input: U256,
     output: U256,
@@ -199,19 +441,101 @@ impl OrderedReserves {
     }
 }
 
+#[derive(serde::Deserialize)]
+struct RawBlacklistedPair {
+    token0: Address,
+    token1: Address,
+}
+
+fn sorted_tokens(token0: Address, token1: Address) -> (Address, Address) {
+    if token0 < token1 {
+        (token0, token1)
+    } else {
+        (token1, token0)
+    }
+}
+
+/// Loads a routing blacklist: specific token pairs to exclude from
+/// pathfinding regardless of which protocol lists them, e.g. a pair that's
+/// individually legitimate but known to be low-liquidity or manipulated. A
+/// missing file means no blacklist, same as an empty one.
+pub async fn load_pair_blacklist(file_path: &str) -> Result<FxHashSet<(Address, Address)>> {
+    let raw_json = match tokio::fs::read_to_string(file_path).await {
+        Ok(contents) => contents,
+        Err(_) => return Ok(FxHashSet::default()),
+    };
+    let raw: Vec<RawBlacklistedPair> = serde_json::from_str(raw_json.as_str())?;
+    Ok(raw
+        .into_iter()
+        .map(|entry| sorted_tokens(entry.token0, entry.token1))
+        .collect())
+}
+
+/// Loads an optional global token allowlist: when present, only pairs where
+/// both tokens are in the set may appear in any searched path, for
+/// operators who want conservative stable/bluechip-only operation instead
+/// of the default "every pair in the DB" universe. A missing file means
+/// allowlist mode is off, same as the absence of any other optional config
+/// file in this bot.
+pub async fn load_token_allowlist(file_path: &str) -> Result<Option<FxHashSet<Address>>> {
+    let raw_json = match tokio::fs::read_to_string(file_path).await {
+        Ok(contents) => contents,
+        Err(_) => return Ok(None),
+    };
+    let tokens: Vec<Address> = serde_json::from_str(raw_json.as_str())?;
+    Ok(Some(tokens.into_iter().collect()))
+}
+
+#[derive(serde::Deserialize)]
+struct RawMinTradeSize {
+    token: Address,
+    min_amount: String,
+}
+
+/// Loads per-token minimum trade sizes: a victim transaction whose entry
+/// amount falls below its token's configured minimum is too small to be
+/// worth the simulate/search stage and is dropped before reaching it.
+/// `min_amount` is a decimal string since token amounts routinely exceed
+/// what a JSON number can represent exactly. A missing file means no
+/// minimums are enforced, same as the absence of any other optional config
+/// file in this bot.
+pub async fn load_min_trade_sizes(file_path: &str) -> Result<FxHashMap<Address, U256>> {
+    let raw_json = match tokio::fs::read_to_string(file_path).await {
+        Ok(contents) => contents,
+        Err(_) => return Ok(FxHashMap::default()),
+    };
+    let raw: Vec<RawMinTradeSize> = serde_json::from_str(raw_json.as_str())?;
+    raw.into_iter()
+        .map(|entry| {
+            let min_amount = U256::from_dec_str(entry.min_amount.as_str()).map_err(|error| {
+                anyhow!("invalid min_amount for token {:?}: {}", entry.token, error)
+            })?;
+            Ok((entry.token, min_amount))
+        })
+        .collect()
+}
+
 pub async fn generate_custom_pairs(
     pair_file: &str,
     client: WSClient,
 ) -> Result<FxHashMap<(Address, Address), Pair>> {
     let mut output: FxHashMap<(Address, Address), Pair> = FxHashMap::default();
-    let custom_pairs: Vec<JsonPair> =
+    let groups: Vec<JsonPairGroup> =
         serde_json::from_str(tokio::fs::read_to_string(pair_file).await?.as_str())?;
 
-    for pair in custom_pairs
-        .into_iter()
-        .map(|json| Pair::from_jsonpair(json, client.clone()))
-    {
-        output.insert((pair.token0, pair.token1), pair);
+    for group in groups {
+        println!(
+            "Loading custom pair group '{}' ({} pairs)",
+            group.name,
+            group.pairs.len()
+        );
+        for pair in group
+            .pairs
+            .into_iter()
+            .map(|json| Pair::from_jsonpair(json, client.clone(), group.default_fee))
+        {
+            output.insert((pair.token0, pair.token1), pair);
+        }
     }
 
     Ok(output)
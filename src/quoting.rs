@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use ethers::prelude::Address;
+use ethers::types::U256;
+use rustc_hash::{FxHashMap, FxHashSet};
+use tokio::sync::RwLock;
+use tokio::time::{interval, Duration};
+use warp::Filter;
+
+use crate::graph::{TokenIndex, DEFAULT_MAX_NUM_SWAPS};
+use crate::pair::{generate_custom_pairs, load_pair_blacklist, Pair};
+use crate::trade::find_best_trade_for_target;
+use crate::v2protocol::{generate_protocols, refresh_all_reserves, update_all_pairs, Protocol, WSClient};
+use crate::{ArbContract, ARBITRAGE_CONTRACT, CUSTOM_PAIRS, NATIVE_TOKEN_ADDRESS, PAIR_BLACKLIST_PATH, PROTOCOLS_PATH};
+
+const RESERVE_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+struct QuotingState {
+    protocols: HashMap<Address, Protocol>,
+    custom_pairs: FxHashMap<(Address, Address), Pair>,
+    pair_blacklist: FxHashSet<(Address, Address)>,
+}
+
+#[derive(serde::Deserialize)]
+struct QuoteQuery {
+    token: String,
+    amount: String,
+}
+
+#[derive(serde::Serialize)]
+struct QuoteResponse {
+    path: Vec<Address>,
+    output: String,
+}
+
+/// Exposes the pathfinder as a small HTTP quoting service: `GET
+/// /quote?token=0x..&amount=..` returns the best path and expected output
+/// across the configured protocols, given `token` as the destination of the
+/// loop (the quote's "traded token"). Keeps its own protocol/pair state,
+/// refreshed on a timer, so other tools can reuse the pathfinding engine
+/// without coupling to the main trading loop's ownership of `TxPool`.
+pub async fn serve(client: WSClient, pool: Arc<deadpool_sqlite::Pool>, addr: SocketAddr) -> Result<()> {
+    let protocols = generate_protocols(client.clone(), PROTOCOLS_PATH.as_str(), pool).await?;
+    let protocols = update_all_pairs(protocols, client.clone()).await?;
+    let mut custom_pairs = generate_custom_pairs(CUSTOM_PAIRS.as_str(), client.clone()).await?;
+    let pair_blacklist = load_pair_blacklist(PAIR_BLACKLIST_PATH.as_str()).await?;
+
+    let arbitrage_contract: ArbContract<WSClient> = ArbContract::new(
+        Address::from_str(ARBITRAGE_CONTRACT.as_str())?,
+        Arc::new(client.clone()),
+    );
+    let wrapped_native = arbitrage_contract.weth().call().await?;
+    let wrap_pair = Pair::new_native_wrap(client.clone(), *NATIVE_TOKEN_ADDRESS, wrapped_native);
+    custom_pairs.insert(wrap_pair.get_tokens(), wrap_pair);
+
+    let state = Arc::new(RwLock::new(QuotingState {
+        protocols,
+        custom_pairs,
+        pair_blacklist,
+    }));
+
+    tokio::spawn(refresh_loop(state.clone()));
+
+    let state_filter = warp::any().map(move || state.clone());
+    let route = warp::path("quote")
+        .and(warp::get())
+        .and(warp::query::<QuoteQuery>())
+        .and(state_filter)
+        .and_then(handle_quote);
+
+    warp::serve(route).run(addr).await;
+    Ok(())
+}
+
+async fn refresh_loop(state: Arc<RwLock<QuotingState>>) {
+    let mut ticker = interval(RESERVE_REFRESH_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let mut guard = state.write().await;
+        if let Err(error) = refresh_all_reserves(&mut guard.protocols).await {
+            println!("Quoting service failed to refresh reserves: {}", error);
+        }
+    }
+}
+
+async fn handle_quote(
+    query: QuoteQuery,
+    state: Arc<RwLock<QuotingState>>,
+) -> std::result::Result<impl warp::Reply, Infallible> {
+    let token = match Address::from_str(&query.token) {
+        Ok(token) => token,
+        Err(_) => return Ok(warp::reply::json(&serde_json::json!({ "error": "invalid token" }))),
+    };
+    let amount = match U256::from_dec_str(&query.amount) {
+        Ok(amount) => amount,
+        Err(_) => return Ok(warp::reply::json(&serde_json::json!({ "error": "invalid amount" }))),
+    };
+
+    let guard = state.read().await;
+    let mut token_index = TokenIndex::new();
+    match find_best_trade_for_target(
+        &guard.protocols,
+        amount,
+        &guard.custom_pairs,
+        token,
+        &guard.pair_blacklist,
+        DEFAULT_MAX_NUM_SWAPS,
+        None,
+        None,
+        *NATIVE_TOKEN_ADDRESS,
+        &mut token_index,
+        None,
+    ) {
+        Ok((path, output)) => Ok(warp::reply::json(&QuoteResponse {
+            path: path.token_order,
+            output: output.to_string(),
+        })),
+        Err(error) => Ok(warp::reply::json(&serde_json::json!({ "error": error.to_string() }))),
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use ethers::prelude::{Address, U256};
+use rustc_hash::FxHashMap;
+
+use crate::graph::{create_graph, find_conversion_path, Path, TokenIndex, DEFAULT_MAX_NUM_SWAPS};
+use crate::pair::Pair;
+use crate::v2protocol::{get_all_pairs, Protocol};
+
+/// Keeps the contract's native-gas balance near `target_native_bps` of its
+/// total working capital (native balance plus the traded-token balance,
+/// valued in native terms via the best on-graph conversion path), so gas
+/// top-ups don't depend on a fixed, externally-funded reserve.
+#[derive(Debug, Clone, Copy)]
+pub struct RebalancePolicy {
+    pub target_native_bps: u32,
+    pub tolerance_bps: u32,
+    pub max_hops: usize,
+    pub slippage_bps: u32,
+}
+
+impl Default for RebalancePolicy {
+    fn default() -> Self {
+        Self {
+            target_native_bps: 2000,
+            tolerance_bps: 1000,
+            max_hops: DEFAULT_MAX_NUM_SWAPS,
+            slippage_bps: 100,
+        }
+    }
+}
+
+/// A planned conversion of `amount_in` of `path`'s first token into its last
+/// token, and the minimum acceptable output after `RebalancePolicy::slippage_bps`.
+pub struct RebalancePlan {
+    pub path: Path,
+    pub amount_in: U256,
+    pub min_output: U256,
+}
+
+/// Decides whether the contract's inventory has drifted far enough from
+/// `policy.target_native_bps` to warrant a conversion and, if so, plans the
+/// swap that would correct it. Returns `None` when already within
+/// `policy.tolerance_bps` of target.
+pub fn plan_rebalance(
+    protocols: &HashMap<Address, Protocol>,
+    custom_pairs: &FxHashMap<(Address, Address), Pair>,
+    traded_token: Address,
+    native_token: Address,
+    traded_balance: U256,
+    native_balance: U256,
+    policy: RebalancePolicy,
+) -> Result<Option<RebalancePlan>> {
+    let mut token_index = TokenIndex::new();
+    let all_pairs = get_all_pairs(protocols.values());
+    let graph = create_graph(all_pairs.chain(custom_pairs.values()), &mut token_index)?;
+
+    let traded_as_native = if traded_balance.is_zero() {
+        U256::zero()
+    } else {
+        find_conversion_path(
+            &graph,
+            &token_index,
+            &traded_token,
+            &native_token,
+            traded_balance,
+            policy.max_hops,
+        )
+        .and_then(|path| path.get_amounts_out(traded_balance, protocols, custom_pairs))
+        .map(|amounts| *amounts.last().unwrap_or(&U256::zero()))
+        .unwrap_or_default()
+    };
+
+    let total_native_value = native_balance.saturating_add(traded_as_native);
+    if total_native_value.is_zero() {
+        return Ok(None);
+    }
+
+    let native_share_bps = native_balance
+        .saturating_mul(U256::from(10_000))
+        .checked_div(total_native_value)
+        .unwrap_or_default()
+        .as_u32();
+
+    let low = policy.target_native_bps.saturating_sub(policy.tolerance_bps);
+    let high = policy.target_native_bps.saturating_add(policy.tolerance_bps);
+
+    let (from, to, amount_in) = if native_share_bps < low {
+        let deficit_bps = (policy.target_native_bps - native_share_bps).min(10_000);
+        let amount_in = traded_balance
+            .saturating_mul(U256::from(deficit_bps))
+            .checked_div(U256::from(10_000))
+            .unwrap_or_default();
+        (traded_token, native_token, amount_in)
+    } else if native_share_bps > high {
+        let surplus_bps = (native_share_bps - policy.target_native_bps).min(10_000);
+        let amount_in = native_balance
+            .saturating_mul(U256::from(surplus_bps))
+            .checked_div(U256::from(10_000))
+            .unwrap_or_default();
+        (native_token, traded_token, amount_in)
+    } else {
+        return Ok(None);
+    };
+
+    if amount_in.is_zero() {
+        return Ok(None);
+    }
+
+    let path = find_conversion_path(&graph, &token_index, &from, &to, amount_in, policy.max_hops)?;
+    let amounts = path.get_amounts_out(amount_in, protocols, custom_pairs)?;
+    let output = *amounts
+        .last()
+        .ok_or_else(|| anyhow!("Conversion path produced no output"))?;
+    let min_output = output
+        .saturating_mul(U256::from(10_000u32.saturating_sub(policy.slippage_bps)))
+        .checked_div(U256::from(10_000))
+        .unwrap_or_default();
+
+    Ok(Some(RebalancePlan {
+        path,
+        amount_in,
+        min_output,
+    }))
+}
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use ethers::prelude::Address;
+use rustc_hash::FxHashMap;
+use tokio::sync::{Mutex, Semaphore};
+
+/// Shared RPC concurrency budget. A single global semaphore bounds the total
+/// number of in-flight on-chain calls across every subsystem that opts in
+/// (reserve refresh, balance/settlement checks, and any future mempool
+/// fetches); a per-protocol semaphore additionally caps how many of those a
+/// single protocol can hold at once, so a large pair sync for one protocol
+/// can't starve latency-critical mempool processing for the rest.
+pub struct RpcBudget {
+    global: Arc<Semaphore>,
+    per_protocol_limit: usize,
+    per_protocol: Mutex<FxHashMap<Address, Arc<Semaphore>>>,
+}
+
+impl RpcBudget {
+    pub fn new(global_limit: usize, per_protocol_limit: usize) -> Self {
+        Self {
+            global: Arc::new(Semaphore::new(global_limit)),
+            per_protocol_limit,
+            per_protocol: Mutex::new(FxHashMap::default()),
+        }
+    }
+
+    async fn protocol_semaphore(&self, protocol: Address) -> Arc<Semaphore> {
+        let mut guard = self.per_protocol.lock().await;
+        guard
+            .entry(protocol)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.per_protocol_limit)))
+            .clone()
+    }
+
+    /// Runs `fut` once both a global and a per-`protocol` permit are
+    /// available, releasing both when it completes.
+    pub async fn run<F: std::future::Future>(&self, protocol: Address, fut: F) -> F::Output {
+        let protocol_semaphore = self.protocol_semaphore(protocol).await;
+        let _protocol_permit = protocol_semaphore
+            .acquire()
+            .await
+            .expect("Semaphore should never be closed");
+        let _global_permit = self
+            .global
+            .acquire()
+            .await
+            .expect("Semaphore should never be closed");
+        fut.await
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Exogenous inputs to the expected-value model that aren't derived from our
+/// own submission history: how likely a transaction we send actually lands
+/// in a block at all, and how likely we are to win the opportunity against
+/// other searchers racing for the same pools.
+#[derive(Debug, Clone, Copy)]
+pub struct EvConfig {
+    pub inclusion_probability_bps: u32,
+    pub competition_win_probability_bps: u32,
+}
+
+/// Running count of mined vs reverted arbitrage submissions, used to derive
+/// an empirical revert probability for the EV model in
+/// `get_profitable_arbitrage`. Seeded with a conservative prior so the
+/// estimate isn't wildly unstable before enough attempts have landed.
+pub struct RevertStats {
+    attempts: AtomicU64,
+    reverts: AtomicU64,
+}
+
+impl RevertStats {
+    const PRIOR_ATTEMPTS: u64 = 20;
+    const PRIOR_REVERT_BPS: u64 = 500;
+
+    pub const fn new() -> Self {
+        Self {
+            attempts: AtomicU64::new(0),
+            reverts: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, reverted: bool) {
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+        if reverted {
+            self.reverts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn revert_probability_bps(&self) -> u32 {
+        let attempts = self.attempts.load(Ordering::Relaxed) + Self::PRIOR_ATTEMPTS;
+        let reverts = self.reverts.load(Ordering::Relaxed)
+            + Self::PRIOR_ATTEMPTS * Self::PRIOR_REVERT_BPS / 10_000;
+        u32::try_from(reverts.saturating_mul(10_000) / attempts).unwrap_or(10_000)
+    }
+}
+
+/// Why a pending router transaction wasn't turned into a `Trade`, so
+/// `DecodingStats` can report *which* kind of gap is widening instead of
+/// just a raw drop count.
+#[derive(Debug, Clone, Copy)]
+pub enum DropReason {
+    /// The first four bytes of calldata don't match any function in the
+    /// router's ABI at all, e.g. a brand-new entrypoint we've never seen.
+    UnknownSelector,
+    /// The selector matched a known function, but its decoded argument
+    /// count or types didn't match the ABI, e.g. a router upgrade that
+    /// changed a function's signature without changing its name.
+    TypeMismatch,
+    /// The function decoded cleanly but isn't one of the swap kinds we have
+    /// an entry for in `router_mappings.json`.
+    NotMapped,
+    /// The trade decoded cleanly but is too small to be worth the
+    /// simulate/search stage, per `min_trade_sizes.json` or
+    /// `MIN_VICTIM_TRADE_RESERVE_BPS`.
+    TooSmall,
+}
+
+/// Per-router tally of how many pending transactions we saw decode cleanly
+/// into a `Trade` versus got dropped, broken down by `DropReason`, so
+/// operators can see when a router upgrade has silently blinded the bot to
+/// that router's flow and prioritize which decoder to add next.
+#[derive(Default)]
+pub struct DecodingStats {
+    decoded: AtomicU64,
+    unknown_selector: AtomicU64,
+    type_mismatch: AtomicU64,
+    not_mapped: AtomicU64,
+    too_small: AtomicU64,
+}
+
+impl DecodingStats {
+    pub fn record_decoded(&self) {
+        self.decoded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self, reason: DropReason) {
+        let counter = match reason {
+            DropReason::UnknownSelector => &self.unknown_selector,
+            DropReason::TypeMismatch => &self.type_mismatch,
+            DropReason::NotMapped => &self.not_mapped,
+            DropReason::TooSmall => &self.too_small,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn total(&self) -> u64 {
+        self.decoded.load(Ordering::Relaxed)
+            + self.unknown_selector.load(Ordering::Relaxed)
+            + self.type_mismatch.load(Ordering::Relaxed)
+            + self.not_mapped.load(Ordering::Relaxed)
+    }
+
+    /// Fraction of observed transactions that decoded successfully, in
+    /// basis points. `10_000` (fully covered) when nothing has been
+    /// observed yet, so an idle router doesn't read as a regression.
+    pub fn coverage_bps(&self) -> u32 {
+        let total = self.total();
+        if total == 0 {
+            return 10_000;
+        }
+        u32::try_from(self.decoded.load(Ordering::Relaxed).saturating_mul(10_000) / total)
+            .unwrap_or(0)
+    }
+
+    pub fn unknown_selector_count(&self) -> u64 {
+        self.unknown_selector.load(Ordering::Relaxed)
+    }
+
+    pub fn type_mismatch_count(&self) -> u64 {
+        self.type_mismatch.load(Ordering::Relaxed)
+    }
+
+    pub fn not_mapped_count(&self) -> u64 {
+        self.not_mapped.load(Ordering::Relaxed)
+    }
+
+    pub fn too_small_count(&self) -> u64 {
+        self.too_small.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use ethers::prelude::{Address, H256};
+
+/// Lifecycle of a transaction we submitted ourselves, as opposed to the
+/// pending trades we observe from other accounts in `TxPool`. There is no
+/// resubmit-with-bumped-gas flow yet, so there's deliberately no `Replaced`
+/// variant - add one only once something actually constructs it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmissionStatus {
+    Pending,
+    Mined,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone)]
+pub struct Submission {
+    pub tx_hash: H256,
+    pub wallet: Address,
+    pub status: SubmissionStatus,
+}
+
+impl Submission {
+    pub const fn new(tx_hash: H256, wallet: Address) -> Self {
+        Self {
+            tx_hash,
+            wallet,
+            status: SubmissionStatus::Pending,
+        }
+    }
+}
+
+/// Largest number of submissions kept around at once. `TxPool` owns one
+/// registry for its whole lifetime (reset on rebuild, same as
+/// `decoding_stats`) rather than one per trade, so this bounds its memory use
+/// over a long run instead of growing without limit.
+const MAX_TRACKED_SUBMISSIONS: usize = 500;
+
+/// Tracks submissions we've broadcast ourselves across this `TxPool`'s
+/// lifetime, from the moment a transaction hash is known (`register`,
+/// `Pending`) through to its resolved outcome (`settle`). Shared behind a
+/// `Mutex` because a trade broadcasts to several wallets concurrently, each
+/// registering and settling its own submission as it resolves - unlike a
+/// registry scoped to a single `execute_trade` call, a submission here is
+/// genuinely observable as `Pending` for the real duration it spends in
+/// flight.
+#[derive(Debug, Default)]
+pub struct SubmissionRegistry {
+    submissions: Mutex<VecDeque<Submission>>,
+}
+
+impl SubmissionRegistry {
+    pub fn register(&self, tx_hash: H256, wallet: Address) {
+        let mut submissions = self.submissions.lock().unwrap();
+        if submissions.len() >= MAX_TRACKED_SUBMISSIONS {
+            submissions.pop_front();
+        }
+        submissions.push_back(Submission::new(tx_hash, wallet));
+        println!("Submission {:?} from {:?} is now pending", tx_hash, wallet);
+    }
+
+    pub fn settle(&self, tx_hash: H256, status: SubmissionStatus) {
+        let mut submissions = self.submissions.lock().unwrap();
+        if let Some(submission) = submissions
+            .iter_mut()
+            .find(|submission| submission.tx_hash == tx_hash)
+        {
+            submission.status = status;
+            println!(
+                "Submission {:?} from {:?} is now {:?}",
+                submission.tx_hash, submission.wallet, submission.status
+            );
+        }
+    }
+
+    pub fn submissions(&self) -> Vec<Submission> {
+        self.submissions.lock().unwrap().iter().cloned().collect()
+    }
+}
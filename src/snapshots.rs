@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::iter::zip;
+use std::sync::Arc;
+
+use anyhow::{anyhow, ensure, Result};
+use deadpool_sqlite::rusqlite::params;
+use deadpool_sqlite::Pool;
+use ethers::abi::{decode, ParamType};
+use ethers::prelude::*;
+use ethers::types::Filter;
+use ethers::utils::keccak256;
+use rand::Rng;
+use rustc_hash::FxHashMap;
+
+use crate::pair::Pair;
+use crate::v2protocol::{Protocol, WSClient};
+
+const SYNC_EVENT_SIGNATURE: &str = "Sync(uint112,uint112)";
+
+/// How to reconstruct a pair's reserves as of a historical block: either ask
+/// the node directly for `getReserves` pinned to that block (accurate for
+/// any block, but only works against a node that still has the state, i.e.
+/// an archive node), or replay `Sync` events from `from_block` up to the
+/// target and take the last one per pair (works against any full node, at
+/// the cost of scanning however many blocks that spans).
+pub enum ReconstructionMode {
+    ArchiveCall,
+    SyncReplay { from_block: u64 },
+}
+
+/// Creates the historical-reserve snapshot table if it doesn't already
+/// exist. A given pair/block combination always reconstructs to the same
+/// values, so rows are replaced rather than accumulated on re-runs.
+pub async fn ensure_schema(pool: &Pool) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.interact(|conn| {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS reserve_snapshots (
+                pair_address TEXT NOT NULL,
+                block_number INTEGER NOT NULL,
+                reserve0 TEXT NOT NULL,
+                reserve1 TEXT NOT NULL,
+                PRIMARY KEY (pair_address, block_number)
+            )",
+            [],
+        )
+    })
+    .await
+    .map_err(|oops| anyhow!(oops.to_string()))??;
+    Ok(())
+}
+
+/// Reconstructs every pair in `protocols`/`custom_pairs` as of
+/// `block_number` using `mode` and writes each one into
+/// `reserve_snapshots`, so a backtest can later load a consistent
+/// cross-section of reserves for a period before the bot was recording live
+/// data. Returns the number of pairs successfully reconstructed; a pair
+/// with no `Sync` events in a `SyncReplay` window is simply skipped rather
+/// than written with a guessed value.
+pub async fn reconstruct_reserves_at_block(
+    client: WSClient,
+    protocols: &HashMap<Address, Protocol>,
+    custom_pairs: &FxHashMap<(Address, Address), Pair>,
+    pool: Arc<Pool>,
+    block_number: u64,
+    mode: ReconstructionMode,
+) -> Result<usize> {
+    let pairs: Vec<&Pair> = protocols
+        .values()
+        .flat_map(|protocol| protocol.pairs.values())
+        .chain(custom_pairs.values())
+        .collect();
+
+    let reserves = match mode {
+        ReconstructionMode::ArchiveCall => {
+            reconstruct_via_archive_call(client, &pairs, block_number).await?
+        }
+        ReconstructionMode::SyncReplay { from_block } => {
+            reconstruct_via_sync_replay(client, &pairs, from_block, block_number).await?
+        }
+    };
+
+    let count = reserves.len();
+    let conn = pool.get().await?;
+    conn.interact(move |conn| -> Result<()> {
+        let tx = conn.transaction()?;
+        for (address, reserve0, reserve1) in reserves {
+            tx.execute(
+                "INSERT OR REPLACE INTO reserve_snapshots
+                 (pair_address, block_number, reserve0, reserve1)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    format!("{:#x}", address),
+                    block_number,
+                    reserve0.to_string(),
+                    reserve1.to_string()
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    })
+    .await
+    .map_err(|oops| anyhow!(oops.to_string()))??;
+
+    Ok(count)
+}
+
+async fn reconstruct_via_archive_call(
+    client: WSClient,
+    pairs: &[&Pair],
+    block_number: u64,
+) -> Result<Vec<(Address, u128, u128)>> {
+    let mut multicall: Multicall<WSClient> = Multicall::new(client, None)
+        .await?
+        .version(MulticallVersion::Multicall)
+        .block(block_number);
+
+    for pair in pairs {
+        multicall.add_call(
+            pair.contract
+                .method::<_, (u128, u128, u32)>("getReserves", ())?,
+            false,
+        );
+    }
+
+    let tokens = multicall.call_raw().await?;
+    ensure!(
+        pairs.len() == tokens.len(),
+        "Differing lengths of pairs and multicall returns"
+    );
+
+    zip(pairs, tokens)
+        .map(|(pair, token)| {
+            let mut reserves = token
+                .into_tuple()
+                .ok_or_else(|| anyhow!("Token cannot convert into tuple"))?;
+            let reserve0 = reserves
+                .swap_remove(0)
+                .into_uint()
+                .ok_or_else(|| anyhow!("Token cannot convert into uint"))?
+                .as_u128();
+            let reserve1 = reserves
+                .swap_remove(1)
+                .into_uint()
+                .ok_or_else(|| anyhow!("Token cannot convert into uint"))?
+                .as_u128();
+            Ok((pair.contract.address(), reserve0, reserve1))
+        })
+        .collect()
+}
+
+async fn reconstruct_via_sync_replay(
+    client: WSClient,
+    pairs: &[&Pair],
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<(Address, u128, u128)>> {
+    let sync_topic = H256::from(keccak256(SYNC_EVENT_SIGNATURE.as_bytes()));
+    let addresses: Vec<Address> = pairs.iter().map(|pair| pair.contract.address()).collect();
+    let filter = Filter::new()
+        .address(addresses)
+        .topic0(sync_topic)
+        .from_block(from_block)
+        .to_block(to_block);
+
+    let logs = client.get_logs(&filter).await?;
+    let mut latest: FxHashMap<Address, (u128, u128)> = FxHashMap::default();
+    for log in logs {
+        let decoded = decode(&[ParamType::Uint(112), ParamType::Uint(112)], &log.data)?;
+        let reserve0 = decoded[0]
+            .clone()
+            .into_uint()
+            .ok_or_else(|| anyhow!("Sync reserve0 not a uint"))?
+            .as_u128();
+        let reserve1 = decoded[1]
+            .clone()
+            .into_uint()
+            .ok_or_else(|| anyhow!("Sync reserve1 not a uint"))?
+            .as_u128();
+        // Logs come back in ascending block order, so the last write for a
+        // given pair is its reserves as of `to_block`.
+        latest.insert(log.address, (reserve0, reserve1));
+    }
+
+    Ok(latest
+        .into_iter()
+        .map(|(address, (reserve0, reserve1))| (address, reserve0, reserve1))
+        .collect())
+}
+
+/// A configurable model of how likely a backtested opportunity is to actually
+/// be captured, rather than lost to a faster or higher-gas competitor before
+/// the bot's own transaction lands. Reconstructed reserves in
+/// `reserve_snapshots` let a backtest replay historical opportunities, but
+/// replaying them as if the bot had no competition wildly overstates the PnL
+/// it would have captured live; this gives such a backtest a way to discount
+/// that. There is no backtest runner in this repo yet to call it from — it's
+/// written as the self-contained piece a future one would plug in at the
+/// point it decides whether a historical opportunity converts into a fill.
+#[derive(Debug, Clone, Copy)]
+pub struct CompetitorModel {
+    /// Mean time, in milliseconds, a competitor takes to notice and react to
+    /// an opportunity. Modelled as an exponential arrival: the probability a
+    /// competitor has already acted by `elapsed_ms` is
+    /// `1 - exp(-elapsed_ms / mean_reaction_ms)`.
+    pub mean_reaction_ms: f64,
+    /// Probability in `[0, 1]` that, conditional on a competitor reacting in
+    /// time, they also outbid the bot's gas price and win the race. Kept
+    /// separate from `mean_reaction_ms` since the two failure modes have
+    /// different causes: arriving late is about detection speed, outbidding
+    /// is about how aggressively the bot prices its own transaction.
+    pub outbid_probability: f64,
+}
+
+impl CompetitorModel {
+    /// A model with no competition at all, for backtests that want to
+    /// measure the bot's own strategy in isolation before layering realism
+    /// on top.
+    pub fn none() -> Self {
+        Self {
+            mean_reaction_ms: f64::INFINITY,
+            outbid_probability: 0.0,
+        }
+    }
+
+    /// Probability that a competitor beats the bot to an opportunity still
+    /// open `elapsed_ms` after it first appeared.
+    pub fn probability_beaten(&self, elapsed_ms: f64) -> f64 {
+        let reacted_in_time = 1.0 - (-elapsed_ms / self.mean_reaction_ms).exp();
+        reacted_in_time * self.outbid_probability
+    }
+
+    /// Rolls whether a competitor beats the bot to an opportunity still open
+    /// `elapsed_ms` after it first appeared, for a backtest deciding whether
+    /// a historical candidate converts into a fill.
+    pub fn is_beaten_by_competitor<R: Rng + ?Sized>(&self, elapsed_ms: f64, rng: &mut R) -> bool {
+        rng.gen_bool(self.probability_beaten(elapsed_ms).clamp(0.0, 1.0))
+    }
+}
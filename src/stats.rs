@@ -0,0 +1,315 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use deadpool_sqlite::rusqlite::params;
+use deadpool_sqlite::Pool;
+use ethers::prelude::Address;
+use ethers::types::{H256, U256};
+use rustc_hash::FxHashMap;
+
+use crate::errors::{ErrorCode, WithErrorCode};
+
+/// Creates the per-pair statistics table if it doesn't already exist. Safe
+/// to call on every startup; unlike `pairs`, this table belongs entirely to
+/// us rather than being externally managed.
+pub async fn ensure_schema(pool: &Pool) -> Result<()> {
+    let conn = pool.get().await.code(ErrorCode::Db)?;
+    conn.interact(|conn| {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS pair_stats (
+                pair_address TEXT PRIMARY KEY,
+                times_routed INTEGER NOT NULL DEFAULT 0,
+                slippage_bps_sum INTEGER NOT NULL DEFAULT 0,
+                slippage_samples INTEGER NOT NULL DEFAULT 0,
+                revert_count INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS wallet_gas_burn (
+                wallet_address TEXT PRIMARY KEY,
+                reverted_attempts INTEGER NOT NULL DEFAULT 0,
+                reverted_gas_wei_sum TEXT NOT NULL DEFAULT '0'
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS recent_opportunities (
+                fingerprint TEXT PRIMARY KEY,
+                block_number INTEGER NOT NULL
+            )",
+            [],
+        )
+    })
+    .await
+    .map_err(|oops| anyhow!(oops.to_string()))?
+    .code(ErrorCode::Db)?;
+    Ok(())
+}
+
+/// Records that we just submitted the opportunity identified by
+/// `fingerprint` at `block_number`, so a restart shortly after can recognize
+/// it was already attempted instead of resubmitting it against reserves that
+/// are now stale.
+pub async fn record_opportunity_submission(
+    pool: Arc<Pool>,
+    fingerprint: H256,
+    block_number: u64,
+) -> Result<()> {
+    let conn = pool.get().await.code(ErrorCode::Db)?;
+    conn.interact(move |conn| -> Result<()> {
+        conn.execute(
+            "INSERT INTO recent_opportunities (fingerprint, block_number) VALUES (?1, ?2)
+             ON CONFLICT(fingerprint) DO UPDATE SET block_number = ?2",
+            params![format!("{:#x}", fingerprint), block_number as i64],
+        )?;
+        Ok(())
+    })
+    .await
+    .map_err(|oops| anyhow!(oops.to_string()))?
+    .code(ErrorCode::Db)
+}
+
+/// Loads the fingerprints of opportunities submitted at or after
+/// `min_block`, alongside the block each was submitted at, pruning anything
+/// older in the same pass so the table doesn't grow without bound. Meant to
+/// be called once at `TxPool` startup to seed replay protection against
+/// resubmitting an opportunity sent just before a restart, from reserves a
+/// fresh sync hasn't caught up past yet. The caller needs the block number
+/// (not just the fingerprint) to derive how much of the opportunity's
+/// cooldown has already elapsed in real time.
+pub async fn load_recent_opportunity_fingerprints(
+    pool: &Pool,
+    min_block: u64,
+) -> Result<FxHashMap<H256, u64>> {
+    let conn = pool.get().await.code(ErrorCode::Db)?;
+    let rows: Vec<(String, i64)> = conn
+        .interact(move |conn| -> Result<Vec<(String, i64)>> {
+            conn.execute(
+                "DELETE FROM recent_opportunities WHERE block_number < ?1",
+                params![min_block as i64],
+            )?;
+            let mut stmt =
+                conn.prepare("SELECT fingerprint, block_number FROM recent_opportunities")?;
+            let mut rows = stmt.query([])?;
+            let mut results = Vec::new();
+            while let Some(row) = rows.next()? {
+                results.push((row.get(0)?, row.get(1)?));
+            }
+            Ok(results)
+        })
+        .await
+        .map_err(|oops| anyhow!(oops.to_string()))?
+        .code(ErrorCode::Db)?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(fingerprint, block_number)| {
+            H256::from_str(fingerprint.as_str())
+                .ok()
+                .map(|fingerprint| (fingerprint, block_number as u64))
+        })
+        .collect())
+}
+
+/// Records that each of `pair_addresses` was part of a path we attempted,
+/// so routing can later learn which pools are routed through often.
+pub async fn record_routed(pool: Arc<Pool>, pair_addresses: Vec<Address>) -> Result<()> {
+    let conn = pool.get().await.code(ErrorCode::Db)?;
+    conn.interact(move |conn| -> Result<()> {
+        for address in pair_addresses {
+            let address = format!("{:#x}", address);
+            conn.execute(
+                "INSERT INTO pair_stats (pair_address, times_routed) VALUES (?1, 1)
+                 ON CONFLICT(pair_address) DO UPDATE SET times_routed = times_routed + 1",
+                params![address],
+            )?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|oops| anyhow!(oops.to_string()))?
+    .code(ErrorCode::Db)
+}
+
+/// Records the divergence (in basis points) between a path's quoted output
+/// and its realized output, attributing it equally to every pool on the
+/// path since we can't isolate which hop actually slipped.
+pub async fn record_slippage(
+    pool: Arc<Pool>,
+    pair_addresses: Vec<Address>,
+    quoted_output: U256,
+    realized_output: U256,
+) -> Result<()> {
+    let slippage_bps = divergence_bps(quoted_output, realized_output);
+    let conn = pool.get().await.code(ErrorCode::Db)?;
+    conn.interact(move |conn| -> Result<()> {
+        for address in pair_addresses {
+            let address = format!("{:#x}", address);
+            conn.execute(
+                "INSERT INTO pair_stats (pair_address, slippage_bps_sum, slippage_samples) VALUES (?1, ?2, 1)
+                 ON CONFLICT(pair_address) DO UPDATE SET
+                     slippage_bps_sum = slippage_bps_sum + ?2,
+                     slippage_samples = slippage_samples + 1",
+                params![address, slippage_bps],
+            )?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|oops| anyhow!(oops.to_string()))?
+    .code(ErrorCode::Db)
+}
+
+/// Records that each of `pair_addresses` was part of a path whose submitted
+/// transaction reverted or otherwise failed.
+pub async fn record_reverts(pool: Arc<Pool>, pair_addresses: Vec<Address>) -> Result<()> {
+    let conn = pool.get().await.code(ErrorCode::Db)?;
+    conn.interact(move |conn| -> Result<()> {
+        for address in pair_addresses {
+            let address = format!("{:#x}", address);
+            conn.execute(
+                "INSERT INTO pair_stats (pair_address, revert_count) VALUES (?1, 1)
+                 ON CONFLICT(pair_address) DO UPDATE SET revert_count = revert_count + 1",
+                params![address],
+            )?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|oops| anyhow!(oops.to_string()))?
+    .code(ErrorCode::Db)
+}
+
+/// Attributes the gas cost of a reverted attempt transaction to the wallet
+/// that sent it, so a wallet stuck submitting doomed transactions (e.g.
+/// racing against a searcher that always wins) shows up as a growing burn
+/// total rather than vanishing into the revert-count-per-pair view, which
+/// can't tell wallets apart. `charges` is `(wallet, gas_used * gas_price)`
+/// for every failed submission in one attempt.
+pub async fn record_failed_attempt_gas(
+    pool: Arc<Pool>,
+    charges: Vec<(Address, U256)>,
+) -> Result<()> {
+    let conn = pool.get().await.code(ErrorCode::Db)?;
+    conn.interact(move |conn| -> Result<()> {
+        for (wallet, gas_cost_wei) in charges {
+            let wallet = format!("{:#x}", wallet);
+            let existing_sum: Option<String> = conn
+                .query_row(
+                    "SELECT reverted_gas_wei_sum FROM wallet_gas_burn WHERE wallet_address = ?1",
+                    params![wallet],
+                    |row| row.get(0),
+                )
+                .ok();
+            let existing_sum = existing_sum
+                .and_then(|value| U256::from_dec_str(&value).ok())
+                .unwrap_or_default();
+            let new_sum = existing_sum.saturating_add(gas_cost_wei).to_string();
+            conn.execute(
+                "INSERT INTO wallet_gas_burn (wallet_address, reverted_attempts, reverted_gas_wei_sum)
+                 VALUES (?1, 1, ?2)
+                 ON CONFLICT(wallet_address) DO UPDATE SET
+                     reverted_attempts = reverted_attempts + 1,
+                     reverted_gas_wei_sum = ?2",
+                params![wallet, new_sum],
+            )?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|oops| anyhow!(oops.to_string()))?
+    .code(ErrorCode::Db)
+}
+
+/// Loads the cumulative reverted-attempt gas burn per wallet, so an operator
+/// can spot a wallet stuck in a revert loop instead of discovering it only
+/// once the wallet runs out of gas.
+pub async fn load_wallet_gas_burn(pool: &Pool) -> Result<Vec<(Address, u64, U256)>> {
+    let conn = pool.get().await.code(ErrorCode::Db)?;
+    let rows: Vec<(String, i64, String)> = conn
+        .interact(|conn| -> Result<Vec<(String, i64, String)>> {
+            let mut stmt = conn
+                .prepare("SELECT wallet_address, reverted_attempts, reverted_gas_wei_sum FROM wallet_gas_burn")?;
+            let mut rows = stmt.query([])?;
+            let mut results = Vec::new();
+            while let Some(row) = rows.next()? {
+                results.push((row.get(0)?, row.get(1)?, row.get(2)?));
+            }
+            Ok(results)
+        })
+        .await
+        .map_err(|oops| anyhow!(oops.to_string()))?
+        .code(ErrorCode::Db)?;
+
+    let mut burns = Vec::with_capacity(rows.len());
+    for (wallet, reverted_attempts, reverted_gas_wei_sum) in rows {
+        let Ok(wallet) = Address::from_str(wallet.as_str()) else {
+            continue;
+        };
+        let reverted_gas_wei_sum = U256::from_dec_str(&reverted_gas_wei_sum).unwrap_or_default();
+        let reverted_attempts = u64::try_from(reverted_attempts).unwrap_or(0);
+        burns.push((wallet, reverted_attempts, reverted_gas_wei_sum));
+    }
+    Ok(burns)
+}
+
+/// Loads a basis-point penalty factor per pair, derived from its observed
+/// revert rate plus its average realized-vs-quoted slippage, meant to be
+/// consumed as a routing penalty by the graph weights. Pairs with no
+/// history are simply absent (zero penalty).
+pub async fn load_penalty_factors(pool: &Pool) -> Result<FxHashMap<Address, u32>> {
+    let conn = pool.get().await.code(ErrorCode::Db)?;
+    let rows: Vec<(String, i64, i64, i64, i64)> = conn
+        .interact(|conn| -> Result<Vec<(String, i64, i64, i64, i64)>> {
+            let mut stmt = conn.prepare(
+                "SELECT pair_address, times_routed, slippage_bps_sum, slippage_samples, revert_count FROM pair_stats",
+            )?;
+            let mut rows = stmt.query([])?;
+            let mut results = Vec::new();
+            while let Some(row) = rows.next()? {
+                results.push((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?));
+            }
+            Ok(results)
+        })
+        .await
+        .map_err(|oops| anyhow!(oops.to_string()))?
+        .code(ErrorCode::Db)?;
+
+    let mut penalties = FxHashMap::default();
+    for (address, times_routed, slippage_bps_sum, slippage_samples, revert_count) in rows {
+        let Ok(address) = Address::from_str(address.as_str()) else {
+            continue;
+        };
+        let revert_penalty_bps = if times_routed > 0 {
+            u32::try_from(revert_count.saturating_mul(10_000) / times_routed).unwrap_or(10_000)
+        } else {
+            0
+        };
+        let slippage_penalty_bps = if slippage_samples > 0 {
+            u32::try_from((slippage_bps_sum / slippage_samples).max(0)).unwrap_or(0)
+        } else {
+            0
+        };
+        let penalty = revert_penalty_bps
+            .saturating_add(slippage_penalty_bps)
+            .min(10_000);
+        if penalty > 0 {
+            penalties.insert(address, penalty);
+        }
+    }
+    Ok(penalties)
+}
+
+fn divergence_bps(quoted: U256, realized: U256) -> i64 {
+    if quoted.is_zero() {
+        return 0;
+    }
+    let diff = quoted.saturating_sub(realized);
+    let bps = diff
+        .saturating_mul(U256::from(10_000))
+        .checked_div(quoted)
+        .unwrap_or_default();
+    i64::try_from(bps.as_u128()).unwrap_or(i64::MAX)
+}
@@ -0,0 +1,135 @@
+use ethers::prelude::{Address, LocalWallet, Signer};
+use rustc_hash::FxHashSet;
+
+use anyhow::Result;
+
+use crate::graph::DEFAULT_MAX_NUM_SWAPS;
+use crate::scoring::EvConfig;
+
+#[derive(Debug, serde::Deserialize)]
+struct RawStrategy {
+    name: String,
+    traded_token: Address,
+    #[serde(default = "default_max_hops")]
+    max_hops: usize,
+    #[serde(default)]
+    token_universe: Option<Vec<Address>>,
+    /// Tokens (other than `traded_token`) the strategy may settle an
+    /// arbitrage in when that's worth more than completing the loop back to
+    /// `traded_token`, e.g. when the final hop home is the unprofitable leg
+    /// of an otherwise-good cycle. Absent by default: the bot only ever
+    /// settles in `traded_token`, as before.
+    #[serde(default)]
+    profit_tokens: Option<Vec<Address>>,
+    /// Additional tokens a cycle may equally start and end on instead of
+    /// `traded_token`, e.g. treating USDC and USDT as interchangeable
+    /// anchors for a stables strategy. Absent by default: the search anchors
+    /// on `traded_token` alone, as before.
+    #[serde(default)]
+    base_tokens: Option<Vec<Address>>,
+    #[serde(default)]
+    inclusion_probability_bps: Option<u32>,
+    #[serde(default)]
+    competition_win_probability_bps: Option<u32>,
+    wallet_addresses: Vec<Address>,
+}
+
+const fn default_max_hops() -> usize {
+    DEFAULT_MAX_NUM_SWAPS
+}
+
+/// One tenant's view of the shared mempool/reserve infrastructure: its own
+/// target token, hop budget, optional restriction to a token universe (e.g.
+/// stables-only), expected-value thresholds, and the wallets it is allowed
+/// to submit from. Every strategy searches and executes independently
+/// against the same `TxPool`.
+pub struct Strategy {
+    pub name: String,
+    pub target: Address,
+    pub max_hops: usize,
+    pub token_universe: Option<FxHashSet<Address>>,
+    pub profit_tokens: Option<FxHashSet<Address>>,
+    pub base_tokens: Option<FxHashSet<Address>>,
+    pub ev_config: EvConfig,
+    pub wallets: Vec<LocalWallet>,
+}
+
+/// Narrows a strategy's own `token_universe` (if any) by the global
+/// allowlist (if any): no allowlist leaves the strategy's setting
+/// untouched, no strategy-level universe but an allowlist adopts the
+/// allowlist outright, and both present intersect, since either one
+/// excluding a token should keep it excluded.
+fn apply_allowlist(
+    token_universe: Option<FxHashSet<Address>>,
+    allowlist: Option<&FxHashSet<Address>>,
+) -> Option<FxHashSet<Address>> {
+    match (token_universe, allowlist) {
+        (universe, None) => universe,
+        (None, Some(allowlist)) => Some(allowlist.clone()),
+        (Some(universe), Some(allowlist)) => {
+            Some(universe.intersection(allowlist).copied().collect())
+        }
+    }
+}
+
+/// Loads strategies from `file_path`, or falls back to a single strategy
+/// covering every configured wallet with the bot's previous single-tenant
+/// defaults when the file doesn't exist. `allowlist`, if set, further
+/// restricts every strategy's token universe to the operator's configured
+/// set regardless of what the strategy itself specifies.
+pub async fn load_strategies(
+    file_path: &str,
+    default_target: Address,
+    default_ev_config: EvConfig,
+    all_wallets: &[LocalWallet],
+    allowlist: Option<&FxHashSet<Address>>,
+) -> Result<Vec<Strategy>> {
+    let raw_text = match tokio::fs::read_to_string(file_path).await {
+        Ok(raw_text) => raw_text,
+        Err(_) => {
+            return Ok(vec![Strategy {
+                name: "default".to_string(),
+                target: default_target,
+                max_hops: DEFAULT_MAX_NUM_SWAPS,
+                token_universe: apply_allowlist(None, allowlist),
+                profit_tokens: None,
+                base_tokens: None,
+                ev_config: default_ev_config,
+                wallets: all_wallets.to_vec(),
+            }])
+        }
+    };
+
+    let raw_strategies: Vec<RawStrategy> = serde_json::from_str(raw_text.as_str())?;
+    let mut strategies = Vec::with_capacity(raw_strategies.len());
+    for raw in raw_strategies {
+        let wallets: Vec<LocalWallet> = all_wallets
+            .iter()
+            .filter(|wallet| raw.wallet_addresses.contains(&wallet.address()))
+            .cloned()
+            .collect();
+
+        strategies.push(Strategy {
+            name: raw.name,
+            target: raw.traded_token,
+            max_hops: raw.max_hops,
+            token_universe: apply_allowlist(
+                raw.token_universe.map(|tokens| tokens.into_iter().collect()),
+                allowlist,
+            ),
+            profit_tokens: raw.profit_tokens.map(|tokens| tokens.into_iter().collect()),
+            base_tokens: raw.base_tokens.map(|tokens| tokens.into_iter().collect()),
+            ev_config: EvConfig {
+                inclusion_probability_bps: raw
+                    .inclusion_probability_bps
+                    .unwrap_or(default_ev_config.inclusion_probability_bps),
+                competition_win_probability_bps: raw
+                    .competition_win_probability_bps
+                    .unwrap_or(default_ev_config.competition_win_probability_bps),
+            },
+            wallets,
+        });
+    }
+
+    Ok(strategies)
+}
@@ -1,19 +1,23 @@
 use std::collections::HashMap;
 use std::iter::zip;
-use std::str::FromStr;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use anyhow::{ensure, Result};
+use tokio::time::Instant;
+
+use anyhow::{anyhow, ensure, Result};
 use ethers::abi::{Detokenize, InvalidOutputType, Token, Tokenizable};
 use ethers::prelude::{Address, U256};
 use ethers::types::H256;
-use petgraph::stable_graph::NodeIndex;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
-use crate::graph::{create_graph, find_shortest_path, PairLookup, Path};
+use crate::chain_profile::ChainProfile;
+use crate::graph::{
+    create_graph, find_conversion_path, find_shortest_path, PairLookup, Path, TokenIndex,
+};
 use crate::pair::Pair;
+use crate::scoring::EvConfig;
 use crate::v2protocol::{get_all_pairs, Protocol};
-use crate::{estimate_gas, TRADED_TOKEN};
+use crate::estimate_gas;
 
 #[derive(Debug)]
 pub enum TradeParams {
@@ -27,7 +31,28 @@ pub struct PossibleArbitrage {
     pub input: U256,
     pub output: U256,
     pub profit: U256,
+    /// Already folds in the expected cost of `execute_trade`'s full
+    /// multi-attempt fan-out (one success plus `TRANSACTION_ATTEMPTS - 1`
+    /// cheaper reverts), via `estimate_gas` — `expected_value` can charge
+    /// this figure directly without re-deriving the attempt count itself.
     pub gas_in_eth: U256,
+    /// The victim trade that made this opportunity profitable, so it can be
+    /// re-checked for staleness right before submission: the opportunity was
+    /// computed against its victim still pending, but by the time we're
+    /// ready to fire that victim may have mined, been replaced, or expired.
+    pub origin_tx_hash: H256,
+    pub origin_protocol: Address,
+    /// Surfaced by a standalone reserve scan rather than a pending victim
+    /// transaction, so `origin_tx_hash`/`origin_protocol` are meaningless
+    /// placeholders and `victim_still_pending` must not look for a victim
+    /// that was never there.
+    pub is_opportunistic: bool,
+    /// Set when this candidate was returned early because a new block
+    /// interrupted the search (see `TxPool::simulate_trades`) rather than
+    /// because the search ran to completion. A later, unseen trade could
+    /// have beaten it, so callers should weigh it less heavily than a
+    /// candidate found by an uninterrupted pass.
+    pub stale_risk: bool,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -37,9 +62,17 @@ pub enum Gas {
 }
 
 impl PossibleArbitrage {
-    pub fn new(path: Path, gas: Gas, output: U256, input: U256) -> Self {
+    pub fn new(
+        path: Path,
+        gas: Gas,
+        output: U256,
+        input: U256,
+        origin_tx_hash: H256,
+        origin_protocol: Address,
+        chain_profile: ChainProfile,
+    ) -> Self {
         let profit = output.saturating_sub(input);
-        let gas_in_eth = estimate_gas(gas);
+        let gas_in_eth = estimate_gas(gas, chain_profile, path.pair_order.len());
         Self {
             path,
             gas,
@@ -47,7 +80,152 @@ impl PossibleArbitrage {
             input,
             profit,
             gas_in_eth,
+            origin_tx_hash,
+            origin_protocol,
+            is_opportunistic: false,
+            stale_risk: false,
+        }
+    }
+
+    /// Like `new`, but for a path that settles in a token other than the
+    /// one it started from (see `Strategy::profit_tokens`), where `output`
+    /// and `input` aren't directly comparable and `profit` must instead be
+    /// supplied pre-converted into a common reporting currency.
+    pub fn with_profit(
+        path: Path,
+        gas: Gas,
+        output: U256,
+        input: U256,
+        profit: U256,
+        origin_tx_hash: H256,
+        origin_protocol: Address,
+        chain_profile: ChainProfile,
+    ) -> Self {
+        let gas_in_eth = estimate_gas(gas, chain_profile, path.pair_order.len());
+        Self {
+            path,
+            gas,
+            output,
+            input,
+            profit,
+            gas_in_eth,
+            origin_tx_hash,
+            origin_protocol,
+            is_opportunistic: false,
+            stale_risk: false,
+        }
+    }
+
+    /// Marks this opportunity as surfaced by a standalone reserve scan
+    /// rather than a specific pending victim transaction, so
+    /// `victim_still_pending` treats it as always fresh instead of looking
+    /// for a victim that was never there.
+    pub fn into_opportunistic(mut self) -> Self {
+        self.is_opportunistic = true;
+        self
+    }
+
+    /// Flags this opportunity as `stale_risk`, see the field's doc comment.
+    pub fn mark_stale_risk(&mut self) {
+        self.stale_risk = true;
+    }
+
+    /// Whether the victim trade that created this opportunity is still
+    /// pending and within its deadline. `trades` is keyed by protocol
+    /// address, so a different pending trade now occupying the same slot
+    /// (same protocol, different hash) correctly counts as stale too.
+    /// Opportunistic candidates (see `is_opportunistic`) have no victim to
+    /// check and are always considered fresh.
+    pub fn victim_still_pending(&self, trades: &FxHashMap<Address, Trade>) -> bool {
+        if self.is_opportunistic {
+            return true;
         }
+        let Some(trade) = trades.get(&self.origin_protocol) else {
+            return false;
+        };
+        if trade.tx_hash != self.origin_tx_hash {
+            return false;
+        }
+        let cur_unix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs();
+        trade.params.get_deadline() >= U256::from(cur_unix)
+    }
+
+    /// Expected value of submitting this opportunity, in wei, given the
+    /// probability of inclusion, the probability of winning the race
+    /// against other searchers, and an empirical revert probability.
+    /// Gas is treated as spent whenever the transaction lands at all
+    /// (win or lose the race); profit is only realised when we also win
+    /// and the call doesn't revert.
+    pub fn expected_value(&self, config: EvConfig, revert_probability_bps: u32) -> i128 {
+        let win_probability_bps = u64::from(config.inclusion_probability_bps)
+            * u64::from(config.competition_win_probability_bps)
+            / 10_000
+            * u64::from(10_000u32.saturating_sub(revert_probability_bps))
+            / 10_000;
+
+        let expected_gain =
+            self.profit.as_u128() as i128 * i128::from(win_probability_bps) / 10_000;
+        let expected_cost = self.gas_in_eth.as_u128() as i128
+            * i128::from(config.inclusion_probability_bps)
+            / 10_000;
+
+        expected_gain - expected_cost
+    }
+
+    /// Cross-checks this opportunity's modeled output against an independent
+    /// on-chain `getAmountsOut` call on one of the path's routers, to catch
+    /// bugs in our own pricing model before we risk gas submitting a trade
+    /// built on a wrong quote. Returns `Ok(true)` when the two agree within
+    /// `max_divergence_bps`, or when no router could be found to check
+    /// against (e.g. a path made entirely of synthetic pairs).
+    pub async fn sanity_check(
+        &self,
+        protocols: &HashMap<Address, Protocol>,
+        max_divergence_bps: u32,
+    ) -> Result<bool> {
+        let Some(router) = self.path.pair_order.iter().find_map(|lookup| {
+            protocols
+                .get(&lookup.factory_address)
+                .map(|protocol| &protocol.router)
+        }) else {
+            return Ok(true);
+        };
+
+        let onchain_amounts: Vec<U256> = router
+            .method::<_, Vec<U256>>(
+                "getAmountsOut",
+                (self.input, self.path.token_order.clone()),
+            )?
+            .call()
+            .await?;
+        let onchain_output = *onchain_amounts
+            .last()
+            .ok_or_else(|| anyhow!("Router returned no amounts"))?;
+
+        Ok(divergence_bps(self.output, onchain_output) <= max_divergence_bps)
+    }
+}
+
+fn divergence_bps(expected: U256, actual: U256) -> u32 {
+    if actual.is_zero() {
+        return if expected.is_zero() { 0 } else { u32::MAX };
+    }
+    let diff = if expected > actual {
+        expected - actual
+    } else {
+        actual - expected
+    };
+    let bps = diff
+        .checked_mul(U256::from(10_000))
+        .and_then(|value| value.checked_div(actual))
+        .unwrap_or(U256::MAX);
+    if bps > U256::from(u32::MAX) {
+        u32::MAX
+    } else {
+        bps.as_u32()
     }
 }
 
@@ -81,6 +259,18 @@ impl TradeParams {
             Self::ExactOutput(item) => item.deadline,
         }
     }
+
+    /// An upper bound on how much of the entry token this trade moves,
+    /// available straight from calldata with no simulation or chain reads.
+    /// `ExactInput` gives this exactly; `ExactOutput` only bounds it by the
+    /// sender's configured slippage, since the actual amount depends on
+    /// reserves at execution time.
+    pub const fn input_amount_bound(&self) -> U256 {
+        match self {
+            Self::ExactInput(item) => item.amount_in,
+            Self::ExactOutput(item) => item.amount_in_max,
+        }
+    }
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Debug)]
@@ -101,6 +291,12 @@ pub struct Trade {
     pub path: Path,
     pub protocol: Address,
     pub simulated: bool,
+    pub first_seen: Instant,
+    /// Whether this trade calls a `SupportingFeeOnTransferTokens` router
+    /// function. Those re-measure the pair's actual received balance rather
+    /// than trusting the nominal `amountIn`, so our own constant-product
+    /// math can't reproduce their real output precisely.
+    pub is_fee_on_transfer: bool,
 }
 
 impl Trade {
@@ -111,6 +307,7 @@ impl Trade {
         params: TradeParams,
         gas: Gas,
         protocol: Address,
+        is_fee_on_transfer: bool,
     ) -> Result<Self> {
         let path = Path::from_trade_tokens(params.get_path(), protocol)?;
         Ok(Self {
@@ -122,10 +319,23 @@ impl Trade {
             path,
             protocol,
             simulated: false,
+            first_seen: Instant::now(),
+            is_fee_on_transfer,
         })
     }
 
-    pub fn simulate(&self, protocol: &mut Protocol, amounts: Vec<U256>) -> Vec<Pair> {
+    /// How long this pending trade has been tracked without settling.
+    pub fn age(&self) -> Duration {
+        self.first_seen.elapsed()
+    }
+
+    /// Applies the victim trade's computed swap amounts back onto each
+    /// hop's reserves, so later `find_best_trade` calls this cycle see the
+    /// pool state as it will be once the victim trade lands. Uses checked
+    /// arithmetic rather than bare `+=`/`-=`: in a release build those wrap
+    /// silently on underflow instead of panicking, which would otherwise
+    /// poison the simulated reserves with a bogus near-u128::MAX value.
+    pub fn simulate(&self, protocol: &mut Protocol, amounts: Vec<U256>) -> Result<Vec<Pair>> {
         let path = &self.path;
         let mut amounts = amounts.windows(2);
         let mut modified_pairs = Vec::new();
@@ -138,16 +348,31 @@ impl Trade {
                 .pairs
                 .get_mut(&pair_key.pair_addresses)
                 .expect("Pair not found in protocol");
-            modified_pairs.push(pair.clone());
-            if input_token == &pair.get_tokens().0 {
-                pair.reserve0 += amount_in;
-                pair.reserve1 -= amount_out;
+            let before = pair.clone();
+
+            let result = if input_token == &pair.get_tokens().0 {
+                pair.reserve0
+                    .checked_add(amount_in)
+                    .zip(pair.reserve1.checked_sub(amount_out))
             } else {
-                pair.reserve0 -= amount_out;
-                pair.reserve1 += amount_in;
+                pair.reserve0
+                    .checked_sub(amount_out)
+                    .zip(pair.reserve1.checked_add(amount_in))
+            };
+
+            match result {
+                Some((reserve0, reserve1)) => {
+                    pair.reserve0 = reserve0;
+                    pair.reserve1 = reserve1;
+                    modified_pairs.push(before);
+                }
+                None => {
+                    protocol.unsimualte_trade(modified_pairs);
+                    return Err(anyhow!("Reserve over/underflow while simulating trade"));
+                }
             }
         }
-        modified_pairs
+        Ok(modified_pairs)
     }
 
     pub fn check_trade_validity(
@@ -155,6 +380,11 @@ impl Trade {
         protocols: &HashMap<Address, Protocol>,
         custom_pairs: &FxHashMap<(Address, Address), Pair>,
     ) -> Result<Vec<U256>> {
+        ensure!(
+            !self.is_fee_on_transfer,
+            "Fee-on-transfer trades can't be modeled with plain constant-product math"
+        );
+
         let cur_unix = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Time went backwards")
@@ -253,22 +483,169 @@ impl Detokenize for SwapExact {
     }
 }
 
-pub fn find_best_trade<'a>(
-    protocols: &'a mut HashMap<Address, Protocol>,
+/// Synthesizes `TradeParams` for a transaction that calls a pair's `swap`
+/// directly instead of going through a router (e.g. a whale skipping the
+/// router hop to save gas). `amount0_out`/`amount1_out` are the pair's own
+/// output slots, of which exactly one must be nonzero for this to be a
+/// plain single-hop swap we can model; `None` for anything else (a
+/// flash-swap drawing both sides, or a no-op call). There's no caller-given
+/// input cap or deadline at this layer - the pool's own invariant check is
+/// the only constraint - so `amount_in_max` and `deadline` are left
+/// unconstrained.
+pub fn direct_pair_swap_params(
+    token0: Address,
+    token1: Address,
+    amount0_out: U256,
+    amount1_out: U256,
+    to: Address,
+) -> Option<TradeParams> {
+    let (path, amount_out) = if !amount0_out.is_zero() && amount1_out.is_zero() {
+        (vec![token1, token0], amount0_out)
+    } else if !amount1_out.is_zero() && amount0_out.is_zero() {
+        (vec![token0, token1], amount1_out)
+    } else {
+        return None;
+    };
+
+    Some(TradeParams::ExactOutput(SwapForExact {
+        amount_out,
+        amount_in_max: U256::MAX,
+        path,
+        to,
+        deadline: U256::MAX,
+    }))
+}
+
+/// Searches for the best arbitrage cycle back to `target` or, if
+/// `base_tokens` is given, to any one of those instead - letting a strategy
+/// anchor its search on more than one base token (e.g. treating USDC and
+/// USDT as equally valid places for a cycle to start and end) rather than
+/// being limited to a single one. Restricted to `max_hops` pairs and (if
+/// given) to a `token_universe` of allowed tokens - the knobs a `Strategy`
+/// uses to carve out its own slice of the shared pair graph, e.g. a tight
+/// stables-only cycle vs. a long-tail search. If `profit_tokens` is given,
+/// also considers terminating the path in one of those tokens instead of
+/// looping all the way back to its anchor, for cycles whose last hop home is
+/// the unprofitable leg; candidates are compared by valuing their output in
+/// `native_token` terms, since a raw alt-token output isn't comparable to an
+/// anchor-denominated one. The winning path's real final-hop output is
+/// returned either way, still denominated in whatever token it actually
+/// ends in.
+/// Fallible instead of panicking: used both by strategies in the main loop
+/// and by the standalone quoting service, neither of which has a single
+/// fixed target.
+pub fn find_best_trade_for_target(
+    protocols: &HashMap<Address, Protocol>,
     amount: U256,
-    custom_pairs: &'a FxHashMap<(Address, Address), Pair>,
-) -> (Path, U256) {
-    let mut nodes: HashMap<Address, NodeIndex> = HashMap::new();
+    custom_pairs: &FxHashMap<(Address, Address), Pair>,
+    target: Address,
+    pair_blacklist: &FxHashSet<(Address, Address)>,
+    max_hops: usize,
+    token_universe: Option<&FxHashSet<Address>>,
+    profit_tokens: Option<&FxHashSet<Address>>,
+    native_token: Address,
+    token_index: &mut TokenIndex,
+    base_tokens: Option<&FxHashSet<Address>>,
+) -> Result<(Path, U256)> {
     let all_pairs = get_all_pairs(protocols.values());
-    let target = Address::from_str(TRADED_TOKEN.as_str()).unwrap();
+    let pairs = all_pairs.chain(custom_pairs.values()).filter(|pair| {
+        if pair.is_routing_blacklisted(pair_blacklist) {
+            return false;
+        }
+        match token_universe {
+            None => true,
+            Some(tokens) => {
+                let (token0, token1) = pair.get_tokens();
+                tokens.contains(&token0) && tokens.contains(&token1)
+            }
+        }
+    });
+
+    let targets: Vec<Address> = match base_tokens {
+        None => vec![target],
+        Some(base_tokens) => std::iter::once(target)
+            .chain(base_tokens.iter().copied())
+            .collect::<FxHashSet<Address>>()
+            .into_iter()
+            .collect(),
+    };
+
+    let graph = create_graph(pairs, token_index)?;
+    let shortest = find_shortest_path(&graph, token_index, &targets, amount, max_hops)?;
+    let outputs = shortest.get_amounts_out(amount, protocols, custom_pairs)?;
+    let output = outputs.last().copied().unwrap_or_default();
+
+    let Some(profit_tokens) = profit_tokens else {
+        return Ok((shortest, output));
+    };
+
+    let value_via_graph = |token: Address, value: U256| -> U256 {
+        if token == native_token {
+            return value;
+        }
+        find_conversion_path(&graph, token_index, &token, &native_token, value, max_hops)
+            .and_then(|path| path.get_amounts_out(value, protocols, custom_pairs))
+            .map(|amounts| amounts.last().copied().unwrap_or_default())
+            .unwrap_or_default()
+    };
+
+    let settlement_anchor = *shortest.token_order.last().unwrap_or(&target);
+    let mut best_path = shortest;
+    let mut best_output = output;
+    let mut best_value = value_via_graph(settlement_anchor, output);
 
-    let pairs = all_pairs.chain(custom_pairs.values());
+    for &alt_token in profit_tokens {
+        if alt_token == settlement_anchor {
+            continue;
+        }
+        let Ok(alt_path) = find_conversion_path(
+            &graph,
+            token_index,
+            &settlement_anchor,
+            &alt_token,
+            amount,
+            max_hops,
+        ) else {
+            continue;
+        };
+        let Ok(alt_outputs) = alt_path.get_amounts_out(amount, protocols, custom_pairs) else {
+            continue;
+        };
+        let alt_output = alt_outputs.last().copied().unwrap_or_default();
+        let alt_value = value_via_graph(alt_token, alt_output);
+        if alt_value > best_value {
+            best_value = alt_value;
+            best_path = alt_path;
+            best_output = alt_output;
+        }
+    }
 
-    let graph = create_graph(pairs, &mut nodes).unwrap();
-    let shortest = find_shortest_path(&graph, nodes, &target, amount).unwrap();
-    let outputs = shortest
-        .get_amounts_out(amount, protocols, custom_pairs)
-        .unwrap();
+    Ok((best_path, best_output))
+}
 
-    (shortest, outputs.last().unwrap().to_owned())
+/// Values `amount` of `token` in `native_token` terms via the best on-graph
+/// conversion path, for comparing amounts held in different tokens (e.g. an
+/// arbitrage that settled in one of a strategy's `profit_tokens` against one
+/// that settled in its usual `target`). Returns zero if no conversion path
+/// exists rather than failing the caller outright.
+pub fn value_in_native(
+    token: Address,
+    amount: U256,
+    native_token: Address,
+    protocols: &HashMap<Address, Protocol>,
+    custom_pairs: &FxHashMap<(Address, Address), Pair>,
+    max_hops: usize,
+    token_index: &mut TokenIndex,
+) -> U256 {
+    if token == native_token {
+        return amount;
+    }
+    let all_pairs = get_all_pairs(protocols.values());
+    let Ok(graph) = create_graph(all_pairs.chain(custom_pairs.values()), token_index) else {
+        return U256::zero();
+    };
+    find_conversion_path(&graph, token_index, &token, &native_token, amount, max_hops)
+        .and_then(|path| path.get_amounts_out(amount, protocols, custom_pairs))
+        .map(|amounts| amounts.last().copied().unwrap_or_default())
+        .unwrap_or_default()
 }
@@ -1,145 +1,443 @@
-use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::iter::zip;
 use std::sync::Arc;
+use std::time::Duration;
 
-use anyhow::{anyhow, ensure, Result};
+use anyhow::Result;
 use deadpool_sqlite::Pool;
 use ethers::abi::{Detokenize, Param, Tokenizable};
 use ethers::prelude::*;
-use futures::future::{join_all, try_join_all};
 use futures::FutureExt;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
 
-use crate::pair::Pair;
-use crate::reload_protocols_and_pairs;
+use crate::chain_profile::ChainProfile;
+use crate::graph::{Path, TokenIndex};
+use crate::pair::{load_min_trade_sizes, load_pair_blacklist, Pair};
+use crate::scoring::{DecodingStats, DropReason};
+use crate::settlement::SubmissionRegistry;
+use crate::stats;
+use crate::strategy::Strategy;
 use crate::trade::TradeParams::{ExactInput, ExactOutput};
 use crate::trade::{
-    find_best_trade, Gas, PossibleArbitrage, SwapExact, SwapForExact, Trade, TradeParams, TradeType,
+    direct_pair_swap_params, find_best_trade_for_target, value_in_native, Gas, PossibleArbitrage,
+    SwapExact, SwapForExact, Trade, TradeParams, TradeType,
 };
 use crate::v2protocol::Protocol;
+use crate::{
+    reload_protocols_and_pairs, MIN_TRADE_SIZES_PATH, MIN_VICTIM_TRADE_RESERVE_BPS,
+    NATIVE_TOKEN_ADDRESS, PAIR_BLACKLIST_PATH, ROUTER_MAP,
+};
 
 pub type WSClient = Arc<Provider<Ws>>;
+/// Pending trades older than this are dropped even if we never observe them
+/// mining or leaving the mempool, keeping `trades` and the per-block
+/// settlement fan-out bounded.
+const MAX_TRADE_AGE: std::time::Duration = std::time::Duration::from_secs(60);
+/// Once we've attempted an opportunity (identified by its path fingerprint),
+/// skip re-surfacing it for this long; otherwise an opportunity that's
+/// repeatedly profitable on paper but keeps losing the race gets retried
+/// every cycle.
+const OPPORTUNITY_COOLDOWN: Duration = Duration::from_secs(5);
+/// How many blocks back a persisted opportunity fingerprint is still
+/// honored when seeding cooldowns on startup. Bounds how long replay
+/// protection survives a restart: old enough and the victim trade it was
+/// chasing has certainly either mined or left the mempool, so there's
+/// nothing left to protect against resubmitting into.
+const REPLAY_PROTECTION_BLOCKS: u64 = 5;
+/// Rough wall-clock duration of one block, used only to translate the
+/// block gap between a persisted fingerprint and the current block into an
+/// approximate elapsed duration when seeding cooldowns on startup. Coarse on
+/// purpose - a seeded entry only needs to land in roughly the right cooldown
+/// state, not be exact to the second.
+const APPROX_BLOCK_TIME: Duration = Duration::from_secs(3);
+/// Width of the sliding window `ThrottleConfig::max_per_minute` is measured
+/// over.
+const SUBMISSION_RATE_WINDOW: Duration = Duration::from_secs(60);
 
-const ROUTER_MAP: &str = "router_mappings.json";
+/// Caps on how many submissions the bot is allowed to attempt in a given
+/// window, so a burst of correlated "profitable" signals — often a symptom
+/// of bad data, e.g. a misread reserve making half the graph look
+/// arbitrageable at once — can't trigger dozens of simultaneous
+/// transactions. `None` in any field leaves that particular cap off,
+/// matching the bot's previous unthrottled behavior.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThrottleConfig {
+    pub max_per_block: Option<usize>,
+    pub max_per_minute: Option<usize>,
+    pub max_concurrent: Option<usize>,
+}
 
-struct FilteredTransactions<'a> {
-    protocol: &'a Protocol,
-    transactions: Vec<Transaction>,
+/// Tracks submissions against a `ThrottleConfig`: how many were attempted
+/// this block, how many fall within the trailing `SUBMISSION_RATE_WINDOW`,
+/// and how many are still unresolved (submitted but not yet released via
+/// `SubmissionThrottle::release`).
+#[derive(Debug, Default)]
+struct SubmissionThrottle {
+    config: ThrottleConfig,
+    this_block: usize,
+    recent_submissions: VecDeque<Instant>,
+    concurrent: usize,
 }
 
-impl<'a> FilteredTransactions<'a> {
-    const fn new(protocol: &'a Protocol) -> Self {
-        let transactions = Vec::new();
+impl SubmissionThrottle {
+    fn new(config: ThrottleConfig) -> Self {
         Self {
-            protocol,
-            transactions,
+            config,
+            this_block: 0,
+            recent_submissions: VecDeque::new(),
+            concurrent: 0,
         }
     }
 
-    async fn decode_transactions(
-        self,
-        transaction_lookup: Arc<HashMap<String, TradeType>>,
-    ) -> Result<Vec<Trade>> {
-        let mut trades = Vec::new();
-        for transaction in &self.transactions {
-            let params = match decode_trade_params(
-                self.protocol.router.borrow(),
-                transaction,
-                transaction_lookup.clone(),
-            )? {
-                Some(param) => param,
-                None => continue,
+    fn reset_block(&mut self) {
+        self.this_block = 0;
+    }
+
+    /// Whether another submission is allowed right now under every
+    /// configured cap. Prunes the rate window as a side effect, so this
+    /// should be called before each candidate submission rather than cached.
+    fn can_submit(&mut self) -> bool {
+        while let Some(oldest) = self.recent_submissions.front() {
+            if oldest.elapsed() > SUBMISSION_RATE_WINDOW {
+                self.recent_submissions.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if let Some(max) = self.config.max_per_block {
+            if self.this_block >= max {
+                return false;
+            }
+        }
+        if let Some(max) = self.config.max_per_minute {
+            if self.recent_submissions.len() >= max {
+                return false;
+            }
+        }
+        if let Some(max) = self.config.max_concurrent {
+            if self.concurrent >= max {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn record_submission(&mut self) {
+        self.this_block += 1;
+        self.recent_submissions.push_back(Instant::now());
+        self.concurrent += 1;
+    }
+
+    fn release(&mut self) {
+        self.concurrent = self.concurrent.saturating_sub(1);
+    }
+}
+
+/// Routes pending router transactions to one tokio task per protocol and
+/// collects the `Trade`s those tasks decode, so decoding one router's flow
+/// never blocks decoding another's, and so decoding in general runs
+/// alongside the next iteration's fetching and searching instead of being
+/// awaited to completion in between them. Workers own everything they need
+/// (a cloned router contract, an `Arc`'d `DecodingStats`/`min_trade_sizes`)
+/// rather than borrowing from `TxPool`, since they outlive any single
+/// `update_trades` call. Dropping a `DecodePipeline` closes its input
+/// channels, which ends every worker's `recv` loop.
+struct DecodePipeline {
+    routes: FxHashMap<Address, mpsc::UnboundedSender<Transaction>>,
+    decoded: mpsc::UnboundedReceiver<Trade>,
+}
+
+impl DecodePipeline {
+    fn new(
+        protocols: &HashMap<Address, Protocol>,
+        tx_lookup: Arc<HashMap<String, TradeType>>,
+        decoding_stats: &FxHashMap<Address, Arc<DecodingStats>>,
+        min_trade_sizes: Arc<FxHashMap<Address, U256>>,
+    ) -> Self {
+        let (decoded_tx, decoded) = mpsc::unbounded_channel();
+        let mut routes = FxHashMap::default();
+
+        for protocol in protocols.values() {
+            let Some(stats) = decoding_stats.get(&protocol.router.address()).cloned() else {
+                continue;
             };
+            let (transactions_tx, transactions_rx) = mpsc::unbounded_channel();
+            for router_address in protocol.router_addresses() {
+                routes.insert(router_address, transactions_tx.clone());
+            }
+
+            tokio::spawn(decode_worker(
+                transactions_rx,
+                protocol.router.clone(),
+                protocol.factory.address(),
+                tx_lookup.clone(),
+                stats,
+                min_trade_sizes.clone(),
+                decoded_tx.clone(),
+            ));
+        }
+
+        Self { routes, decoded }
+    }
+
+    /// Hands a transaction to its router's decode worker. Transactions for
+    /// an address that isn't a known router are silently ignored, same as
+    /// `filter_router_transactions` did before this pipeline existed.
+    fn dispatch(&self, transaction: Transaction) {
+        let Some(to) = transaction.to else { return };
+        if let Some(sender) = self.routes.get(&to) {
+            // Only fails if every receiver (i.e. the worker) has already
+            // been dropped, which only happens when `TxPool` itself is
+            // being torn down; nothing useful to do with the transaction
+            // in that case.
+            let _ = sender.send(transaction);
+        }
+    }
+
+    /// Collects every `Trade` a worker has finished decoding since the last
+    /// call, without waiting for more to arrive.
+    fn drain_trades(&mut self) -> Vec<Trade> {
+        let mut trades = Vec::new();
+        while let Ok(trade) = self.decoded.try_recv() {
+            trades.push(trade);
+        }
+        trades
+    }
+}
 
-            let gas = match transaction.transaction_type {
-                None => Gas::Legacy(
+async fn decode_worker(
+    mut transactions: mpsc::UnboundedReceiver<Transaction>,
+    router: ethers::contract::Contract<WSClient>,
+    factory_address: Address,
+    transaction_lookup: Arc<HashMap<String, TradeType>>,
+    decoding_stats: Arc<DecodingStats>,
+    min_trade_sizes: Arc<FxHashMap<Address, U256>>,
+    decoded: mpsc::UnboundedSender<Trade>,
+) {
+    while let Some(transaction) = transactions.recv().await {
+        let Ok(outcome) = decode_trade_params(&router, &transaction, transaction_lookup.clone())
+        else {
+            continue;
+        };
+        let (params, is_fee_on_transfer) = match outcome {
+            DecodeOutcome::Decoded(params, is_fee_on_transfer) => {
+                decoding_stats.record_decoded();
+                (params, is_fee_on_transfer)
+            }
+            DecodeOutcome::Dropped(reason) => {
+                decoding_stats.record_dropped(reason);
+                continue;
+            }
+        };
+
+        let gas = gas_from_transaction(&transaction);
+        let Some(to) = transaction.to else { continue };
+        let Ok(trade) = Trade::new(
+            transaction.hash,
+            to,
+            transaction.from,
+            params,
+            gas,
+            factory_address,
+            is_fee_on_transfer,
+        ) else {
+            continue;
+        };
+
+        if below_min_trade_size(&trade, &min_trade_sizes) {
+            decoding_stats.record_dropped(DropReason::TooSmall);
+            continue;
+        }
+
+        if decoded.send(trade).is_err() {
+            return;
+        }
+    }
+}
+
+/// The absolute half of the configured trade-size filters: whether a
+/// decoded trade's entry token has a configured minimum in
+/// `min_trade_sizes.json` that its input amount falls short of. Doesn't
+/// need live reserve data, so it can run inside a decode worker; the other
+/// half (minimum share of pool reserves) needs `TxPool`'s live `protocols`
+/// map and is applied back on the main task in `update_trades` instead.
+fn below_min_trade_size(trade: &Trade, min_trade_sizes: &FxHashMap<Address, U256>) -> bool {
+    let Some(&entry_token) = trade.path.token_order.first() else {
+        return false;
+    };
+    let Some(&minimum) = min_trade_sizes.get(&entry_token) else {
+        return false;
+    };
+    trade.params.input_amount_bound() < minimum
+}
+
+const FEE_ON_TRANSFER_SUFFIX: &str = "SupportingFeeOnTransferTokens";
+
+fn gas_from_transaction(transaction: &Transaction) -> Gas {
+    match transaction.transaction_type {
+        None => Gas::Legacy(
+            transaction
+                .gas_price
+                .expect("Gas price expected for legacy"),
+        ),
+        Some(num) => {
+            if num.as_u64() == 0 {
+                Gas::Legacy(
                     transaction
                         .gas_price
                         .expect("Gas price expected for legacy"),
-                ),
-                Some(num) => {
-                    if num.as_u64() == 0 {
-                        Gas::Legacy(
-                            transaction
-                                .gas_price
-                                .expect("Gas price expected for legacy"),
-                        )
-                    } else {
-                        Gas::London(
-                            transaction
-                                .max_fee_per_gas
-                                .expect("MFPG expected for london"),
-                            transaction
-                                .max_priority_fee_per_gas
-                                .expect("MPFPG expected for london"),
-                        )
-                    }
-                }
-            };
-            let to = transaction
-                .to
-                .ok_or_else(|| anyhow!("Trade should have to parameter"))?;
-            let trade = Trade::new(
-                transaction.hash,
-                to,
-                transaction.from,
-                params,
-                gas,
-                self.protocol.factory.address(),
-            )?;
-            trades.push(trade);
+                )
+            } else {
+                Gas::London(
+                    transaction
+                        .max_fee_per_gas
+                        .expect("MFPG expected for london"),
+                    transaction
+                        .max_priority_fee_per_gas
+                        .expect("MPFPG expected for london"),
+                )
+            }
         }
+    }
+}
 
-        Ok(trades)
+/// Locates a known pair by the token pair it trades and the factory it was
+/// created under, mirroring the dual lookup (protocol pairs, then customs)
+/// used everywhere else a `PairLookup` is resolved back to a `Pair`.
+fn lookup_pair<'a>(
+    factory_address: Address,
+    token_pair: (Address, Address),
+    protocols: &'a HashMap<Address, Protocol>,
+    custom_pairs: &'a FxHashMap<(Address, Address), Pair>,
+) -> Option<&'a Pair> {
+    match protocols.get(&factory_address) {
+        Some(protocol) => protocol.pairs.get(&token_pair),
+        None => custom_pairs.get(&token_pair),
     }
 }
 
+/// Matches and decodes pending transactions that call a known pair's
+/// `swap` directly, identified by `to` being one of `pair_index`'s pair
+/// contracts rather than any of our known routers. Unlike router decoding,
+/// a transaction that happens to call a pair with some other function (e.g.
+/// `sync`, `skim`) is silently skipped rather than tallied as a drop: it
+/// was never a trade to begin with.
+fn decode_direct_pair_swaps(
+    transactions: &[Transaction],
+    pair_index: &FxHashMap<Address, (Address, (Address, Address))>,
+    protocols: &HashMap<Address, Protocol>,
+    custom_pairs: &FxHashMap<(Address, Address), Pair>,
+) -> Vec<Trade> {
+    let mut trades = Vec::new();
+    for transaction in transactions {
+        let Some(to) = transaction.to else { continue };
+        let Some(&(factory_address, token_pair)) = pair_index.get(&to) else {
+            continue;
+        };
+        let Some(pair) = lookup_pair(factory_address, token_pair, protocols, custom_pairs) else {
+            continue;
+        };
+        if transaction.input.len() < 4 {
+            continue;
+        }
+        let signature: Selector = match transaction.input[0..4].try_into() {
+            Ok(signature) => signature,
+            Err(_) => continue,
+        };
+        let Ok(swap_fn) = pair.contract.abi().function("swap") else {
+            continue;
+        };
+        if signature != swap_fn.short_signature() {
+            continue;
+        }
+        let Ok(inputs) = swap_fn.decode_input(&transaction.input[4..]) else {
+            continue;
+        };
+        if inputs.len() != 4 {
+            continue;
+        }
+        let amount0_out = inputs[0].clone().into_uint();
+        let amount1_out = inputs[1].clone().into_uint();
+        let recipient = inputs[2].clone().into_address();
+        let (Some(amount0_out), Some(amount1_out), Some(recipient)) =
+            (amount0_out, amount1_out, recipient)
+        else {
+            continue;
+        };
+        let (token0, token1) = pair.get_tokens();
+        let Some(params) =
+            direct_pair_swap_params(token0, token1, amount0_out, amount1_out, recipient)
+        else {
+            continue;
+        };
+
+        let gas = gas_from_transaction(transaction);
+        let Ok(trade) = Trade::new(
+            transaction.hash,
+            to,
+            transaction.from,
+            params,
+            gas,
+            factory_address,
+            false,
+        ) else {
+            continue;
+        };
+        trades.push(trade);
+    }
+    trades
+}
+
+/// Result of attempting to turn one pending transaction's calldata into
+/// `TradeParams`, distinguishing the ways decoding can legitimately fail
+/// from an outright hard error (e.g. malformed calldata too short to hold a
+/// selector), so callers can tally the former as `DropReason`s without
+/// treating them as failures of the whole decode pass.
+enum DecodeOutcome {
+    Decoded(TradeParams, bool),
+    Dropped(DropReason),
+}
+
 fn decode_trade_params(
     router: &ethers::contract::Contract<WSClient>,
     transaction: &Transaction,
     trade_type_lookup: Arc<HashMap<String, TradeType>>,
-) -> Result<Option<TradeParams>> {
+) -> Result<DecodeOutcome> {
     let signature: &Selector = transaction.input[0..4].try_into()?;
-    let function_name = &router
-        .methods
-        .get(signature)
-        .ok_or_else(|| anyhow!("Selector not found in function"))?
-        .0;
+    let Some(function_name) = router.methods.get(signature).map(|method| &method.0) else {
+        return Ok(DecodeOutcome::Dropped(DropReason::UnknownSelector));
+    };
     let mut inputs = router.decode_with_selector_raw(*signature, &transaction.input)?;
     let params = get_params_from_name(function_name, router)?;
-    ensure!(
-        inputs.len() == params.len(),
-        "Inputs do not match parameters"
-    );
-    ensure!(
-        zip(inputs.clone(), params).all(|(token, parameter)| token.type_check(&parameter.kind)),
-        "Inputs do not match expected parameter types"
-    );
+    let types_match = inputs.len() == params.len()
+        && zip(inputs.clone(), params).all(|(token, parameter)| token.type_check(&parameter.kind));
+    if !types_match {
+        return Ok(DecodeOutcome::Dropped(DropReason::TypeMismatch));
+    }
 
     let trade_type = match trade_type_lookup.get(function_name) {
-        None => return Ok(None),
+        None => return Ok(DecodeOutcome::Dropped(DropReason::NotMapped)),
         Some(trade) => trade,
     };
+    let is_fee_on_transfer = function_name.ends_with(FEE_ON_TRANSFER_SUFFIX);
 
-    let outcome = match trade_type {
+    let params = match trade_type {
         TradeType::ExactEth => {
             inputs.insert(0, transaction.value.into_token());
-            SwapExact::from_tokens(inputs).map(|item| Some(ExactInput(item)))?
-        }
-        TradeType::ExactOther => {
-            SwapExact::from_tokens(inputs).map(|item| Some(ExactInput(item)))?
+            SwapExact::from_tokens(inputs).map(ExactInput)?
         }
+        TradeType::ExactOther => SwapExact::from_tokens(inputs).map(ExactInput)?,
         TradeType::EthForExact => {
             inputs.insert(1, transaction.value.into_token());
-            SwapForExact::from_tokens(inputs).map(|item| Some(ExactOutput(item)))?
-        }
-        TradeType::OtherForExact => {
-            SwapForExact::from_tokens(inputs).map(|item| Some(ExactOutput(item)))?
+            SwapForExact::from_tokens(inputs).map(ExactOutput)?
         }
+        TradeType::OtherForExact => SwapForExact::from_tokens(inputs).map(ExactOutput)?,
     };
 
-    Ok(outcome)
+    Ok(DecodeOutcome::Decoded(params, is_fee_on_transfer))
 }
 
 fn get_params_from_name(
@@ -152,12 +450,44 @@ fn get_params_from_name(
 }
 
 pub struct TxPool<'a> {
-    client: WSClient,
     watcher: Watcher<'a>,
     pub(crate) protocols: HashMap<Address, Protocol>,
     tx_lookup: Arc<HashMap<String, TradeType>>,
     trades: FxHashMap<Address, Trade>,
     pub(crate) custom_pairs: FxHashMap<(Address, Address), Pair>,
+    in_flight_pools: FxHashSet<Address>,
+    opportunity_cooldowns: FxHashMap<H256, Instant>,
+    pair_blacklist: FxHashSet<(Address, Address)>,
+    /// Keyed by router address, tracking how much of that router's pending
+    /// flow we're actually able to decode. Shared with `decode_pipeline`'s
+    /// workers, which record into it directly; reset whenever the `TxPool`
+    /// itself is rebuilt, since a rebuild also means a freshly-resolved
+    /// router set.
+    decoding_stats: FxHashMap<Address, Arc<DecodingStats>>,
+    /// Maps a pair contract's own address to the factory/token-pair key
+    /// needed to look it back up in `protocols`/`custom_pairs`, so a
+    /// transaction that calls a pair's `swap` directly (no router hop) can
+    /// still be recognized and decoded.
+    pair_index: FxHashMap<Address, (Address, (Address, Address))>,
+    /// Per-protocol decode workers fed from `update_trades`; see
+    /// `DecodePipeline`.
+    decode_pipeline: DecodePipeline,
+    /// The chain this `TxPool` is running against, consulted when pricing a
+    /// candidate's gas cost so a single flat `GAS_ESTIMATE` doesn't get
+    /// applied to chains whose gas usage looks nothing like mainnet's.
+    chain_profile: ChainProfile,
+    /// Persistent token→node-index assignment for `protocols`/`custom_pairs`,
+    /// built once here and reused by every `simulate_trades` search instead
+    /// of being rehashed from scratch per path-search, since `protocols` and
+    /// `custom_pairs` are fixed for this `TxPool`'s lifetime (a rebuild gets
+    /// a fresh `TxPool`, and with it a fresh index).
+    token_index: TokenIndex,
+    /// Enforces `ThrottleConfig`'s caps across submissions from this
+    /// `TxPool`'s lifetime; see `SubmissionThrottle`.
+    submission_throttle: SubmissionThrottle,
+    /// Tracks the lifecycle of transactions we've broadcast ourselves across
+    /// this `TxPool`'s lifetime; see `SubmissionRegistry`.
+    pub(crate) submission_registry: Arc<SubmissionRegistry>,
 }
 
 struct Watcher<'a> {
@@ -181,42 +511,335 @@ impl<'a> TxPool<'a> {
         client_arc: WSClient,
         client_ref: &'a Provider<Ws>,
         pool: Arc<Pool>,
+        chain_profile: ChainProfile,
+        submission_throttle: ThrottleConfig,
     ) -> Result<TxPool<'a>> {
-        let tx_lookup: HashMap<String, TradeType> =
-            serde_json::from_str(tokio::fs::read_to_string(ROUTER_MAP).await?.as_str())?;
+        let tx_lookup: HashMap<String, TradeType> = serde_json::from_str(
+            tokio::fs::read_to_string(ROUTER_MAP.as_str())
+                .await?
+                .as_str(),
+        )?;
         let tx_lookup = Arc::new(tx_lookup);
         let (protocols, custom_pairs) =
             reload_protocols_and_pairs(client_arc.clone(), pool.clone())
                 .await
                 .unwrap();
+        let pair_blacklist = load_pair_blacklist(PAIR_BLACKLIST_PATH.as_str()).await?;
+        let min_trade_sizes = load_min_trade_sizes(MIN_TRADE_SIZES_PATH.as_str()).await?;
+
+        // Seed the cooldown map from opportunities we submitted just before
+        // this process last restarted, so a path we already sent against
+        // reserves at block N doesn't get re-detected and resubmitted the
+        // moment the fresh `TxPool` starts searching again. Each entry is
+        // back-dated by its real age (in blocks, converted to an
+        // approximate duration) rather than stamped with `Instant::now()`,
+        // so its remaining cooldown reflects how long ago it actually was
+        // submitted instead of restarting the clock from process start.
+        let current_block = client_arc.get_block_number().await?.as_u64();
+        let opportunity_cooldowns: FxHashMap<H256, Instant> =
+            stats::load_recent_opportunity_fingerprints(
+                &pool,
+                current_block.saturating_sub(REPLAY_PROTECTION_BLOCKS),
+            )
+            .await?
+            .into_iter()
+            .map(|(fingerprint, block_number)| {
+                let blocks_elapsed = current_block.saturating_sub(block_number) as u32;
+                let age = APPROX_BLOCK_TIME.saturating_mul(blocks_elapsed);
+                (fingerprint, Instant::now() - age)
+            })
+            .collect();
 
         let watcher = Watcher::new(client_ref).await?;
 
+        let decoding_stats: FxHashMap<Address, Arc<DecodingStats>> = protocols
+            .values()
+            .map(|protocol| {
+                (
+                    protocol.router.address(),
+                    Arc::new(DecodingStats::default()),
+                )
+            })
+            .collect();
+
+        let pair_index = protocols
+            .values()
+            .flat_map(|protocol| protocol.pairs.values())
+            .chain(custom_pairs.values())
+            .map(|pair| (pair.contract.address(), (pair.factory_address, pair.get_tokens())))
+            .collect();
+
+        let mut token_index = TokenIndex::new();
+        for pair in protocols
+            .values()
+            .flat_map(|protocol| protocol.pairs.values())
+            .chain(custom_pairs.values())
+        {
+            token_index.register_pair(pair);
+        }
+
+        let decode_pipeline = DecodePipeline::new(
+            &protocols,
+            tx_lookup.clone(),
+            &decoding_stats,
+            Arc::new(min_trade_sizes),
+        );
+
         Ok(Self {
-            client: client_arc,
             protocols,
             tx_lookup,
             watcher,
             trades: FxHashMap::default(),
             custom_pairs,
+            in_flight_pools: FxHashSet::default(),
+            opportunity_cooldowns,
+            pair_blacklist,
+            decoding_stats,
+            pair_index,
+            decode_pipeline,
+            chain_profile,
+            token_index,
+            submission_throttle: SubmissionThrottle::new(submission_throttle),
+            submission_registry: Arc::new(SubmissionRegistry::default()),
         })
     }
 
-    pub async fn get_arbitrages(&mut self, input: U256) -> Result<Vec<PossibleArbitrage>> {
+    /// Per-router decoding coverage since this `TxPool` was built: basis
+    /// points of observed pending transactions that decoded into a `Trade`,
+    /// alongside the raw unknown-selector, type-mismatch and not-mapped drop
+    /// counts (`TooSmall` drops are not included), so an operator can tell a
+    /// router that's gone quiet from one that's actively failing to decode.
+    pub fn decoding_coverage(&self) -> Vec<(Address, u32, u64, u64, u64)> {
+        self.decoding_stats
+            .iter()
+            .map(|(router, stats)| {
+                (
+                    *router,
+                    stats.coverage_bps(),
+                    stats.unknown_selector_count(),
+                    stats.type_mismatch_count(),
+                    stats.not_mapped_count(),
+                )
+            })
+            .collect()
+    }
+
+    /// Records that we just attempted the opportunity identified by `path`,
+    /// so `simulate_trades` won't re-surface it until `OPPORTUNITY_COOLDOWN`
+    /// elapses.
+    pub fn note_attempted(&mut self, path: &Path) {
+        self.opportunity_cooldowns
+            .retain(|_, attempted_at| attempted_at.elapsed() < OPPORTUNITY_COOLDOWN);
+        self.opportunity_cooldowns
+            .insert(path.fingerprint(), Instant::now());
+    }
+
+    /// Re-checks an already-computed opportunity against the current trade
+    /// pool: its victim trade may have mined, been replaced, or expired in
+    /// the time since `simulate_trades` produced it.
+    pub fn is_opportunity_still_valid(&self, arbitrage: &PossibleArbitrage) -> bool {
+        arbitrage.victim_still_pending(&self.trades)
+    }
+
+    /// Whether any pool on `path` is already reserved by another in-flight
+    /// submission, e.g. a higher-value opportunity from the same
+    /// iteration's ranking that claimed it first. Callers use this to
+    /// preempt a lower-priority candidate instead of submitting a
+    /// transaction doomed to race (and likely lose to) one of our own.
+    pub fn path_conflicts_with_in_flight(&self, path: &Path) -> bool {
+        path_conflicts_with_in_flight(
+            path,
+            &self.in_flight_pools,
+            &self.protocols,
+            &self.custom_pairs,
+        )
+    }
+
+    fn is_cooling_down(&self, path: &Path) -> bool {
+        match self.opportunity_cooldowns.get(&path.fingerprint()) {
+            Some(attempted_at) => attempted_at.elapsed() < OPPORTUNITY_COOLDOWN,
+            None => false,
+        }
+    }
+
+    /// Marks the pools touched by `path` as occupied by one of our own
+    /// submissions so they are excluded from new arbitrages until released.
+    pub fn reserve_in_flight_pools(&mut self, path: &Path) -> Result<()> {
+        for pool in path.pool_addresses(&self.protocols, &self.custom_pairs)? {
+            self.in_flight_pools.insert(pool);
+        }
+        Ok(())
+    }
+
+    /// Releases pools previously reserved with `reserve_in_flight_pools` once
+    /// our submission has settled (mined, failed or otherwise resolved).
+    pub fn release_in_flight_pools(&mut self, path: &Path) -> Result<()> {
+        for pool in path.pool_addresses(&self.protocols, &self.custom_pairs)? {
+            self.in_flight_pools.remove(&pool);
+        }
+        Ok(())
+    }
+
+    /// Whether a new submission is allowed under the configured
+    /// per-block/per-minute/concurrent caps (see `ThrottleConfig`). Callers
+    /// should check this immediately before submitting, since it also prunes
+    /// the rate window.
+    pub fn submission_allowed(&mut self) -> bool {
+        self.submission_throttle.can_submit()
+    }
+
+    /// Records a just-attempted submission against every configured cap.
+    /// Pair with `release_submission` once it settles.
+    pub fn note_submission(&mut self) {
+        self.submission_throttle.record_submission();
+    }
+
+    /// Releases a submission counted by `note_submission` from the
+    /// concurrent cap once it has settled (mined, failed or otherwise
+    /// resolved).
+    pub fn release_submission(&mut self) {
+        self.submission_throttle.release();
+    }
+
+    /// Resets the per-block submission count; called once per new block.
+    pub fn reset_block_submissions(&mut self) {
+        self.submission_throttle.reset_block();
+    }
+
+    /// Searches for arbitrage opportunities on behalf of every strategy at
+    /// once, so each pending victim trade only has to be simulated against
+    /// the shared reserves a single time. `inputs[i]` is the amount to
+    /// offer `strategies[i]`; the returned `Vec` is in the same order.
+    ///
+    /// `block_subscription` is polled (without blocking) once per trade
+    /// while searching; if a new block arrives mid-search, the search is
+    /// cut short and whatever was already found is returned flagged
+    /// `stale_risk` (see `simulate_trades`) rather than thrown away or left
+    /// to block block processing until the full pass finishes. A block
+    /// consumed this way is returned alongside the results so the caller can
+    /// still run its usual new-block handling instead of losing it.
+    pub async fn get_arbitrages(
+        &mut self,
+        strategies: &[Strategy],
+        inputs: &[U256],
+        block_subscription: &mut SubscriptionStream<'_, Ws, Block<H256>>,
+    ) -> Result<(Vec<Vec<PossibleArbitrage>>, Option<Block<H256>>)> {
         self.update_trades().await?;
-        Ok(self.simulate_trades(input))
+        Ok(self.simulate_trades(strategies, inputs, block_subscription))
+    }
+
+    /// Runs one search pass per strategy against the reserves as they stand
+    /// right now, independent of any pending victim transaction, so an
+    /// imbalance left behind by someone else's already-mined trade isn't
+    /// missed just because nothing of ours is currently tracking it in the
+    /// mempool. `inputs[i]` is the amount to offer `strategies[i]`, same as
+    /// `get_arbitrages`. Candidates are tagged via
+    /// `PossibleArbitrage::into_opportunistic` and filtered by the same
+    /// cooldown/in-flight-pool checks `simulate_trades` applies to
+    /// victim-driven ones.
+    pub fn find_opportunistic_arbitrages(
+        &self,
+        strategies: &[Strategy],
+        inputs: &[U256],
+        gas: Gas,
+    ) -> Vec<Option<PossibleArbitrage>> {
+        // `&self` here (unlike `simulate_trades`) can't reuse `self.token_index`,
+        // so this call's worth of searches shares one freshly-built index
+        // instead of each strategy rehashing its own.
+        let mut token_index = TokenIndex::new();
+        strategies
+            .iter()
+            .zip(inputs)
+            .map(|(strategy, &amount)| {
+                let (path, output) = find_best_trade_for_target(
+                    &self.protocols,
+                    amount,
+                    &self.custom_pairs,
+                    strategy.target,
+                    &self.pair_blacklist,
+                    strategy.max_hops,
+                    strategy.token_universe.as_ref(),
+                    strategy.profit_tokens.as_ref(),
+                    *NATIVE_TOKEN_ADDRESS,
+                    &mut token_index,
+                    strategy.base_tokens.as_ref(),
+                )
+                .ok()?;
+                if self.is_cooling_down(&path)
+                    || path_conflicts_with_in_flight(
+                        &path,
+                        &self.in_flight_pools,
+                        &self.protocols,
+                        &self.custom_pairs,
+                    )
+                {
+                    return None;
+                }
+                let settled_in_target = path.token_order.last() == Some(&strategy.target);
+                let arbitrage = if settled_in_target {
+                    PossibleArbitrage::new(
+                        path,
+                        gas,
+                        output,
+                        amount,
+                        H256::zero(),
+                        Address::zero(),
+                        self.chain_profile,
+                    )
+                } else {
+                    let settlement_token = *path.token_order.last().unwrap();
+                    let output_in_native = value_in_native(
+                        settlement_token,
+                        output,
+                        *NATIVE_TOKEN_ADDRESS,
+                        &self.protocols,
+                        &self.custom_pairs,
+                        strategy.max_hops,
+                        &mut token_index,
+                    );
+                    let input_in_native = value_in_native(
+                        strategy.target,
+                        amount,
+                        *NATIVE_TOKEN_ADDRESS,
+                        &self.protocols,
+                        &self.custom_pairs,
+                        strategy.max_hops,
+                        &mut token_index,
+                    );
+                    let profit = output_in_native.saturating_sub(input_in_native);
+                    PossibleArbitrage::with_profit(
+                        path,
+                        gas,
+                        output,
+                        amount,
+                        profit,
+                        H256::zero(),
+                        Address::zero(),
+                        self.chain_profile,
+                    )
+                };
+                Some(arbitrage.into_opportunistic())
+            })
+            .collect()
     }
 
     async fn update_trades(&mut self) -> Result<()> {
         let new_transactions = self.get_new_transactions().await;
 
-        let mut futures = Vec::new();
-        let filtered = self.filter_router_transactions(new_transactions);
-        for filter in filtered.into_iter() {
-            futures.push(filter.decode_transactions(self.tx_lookup.clone()));
+        let direct_swap_trades = decode_direct_pair_swaps(
+            &new_transactions,
+            &self.pair_index,
+            &self.protocols,
+            &self.custom_pairs,
+        );
+
+        for transaction in new_transactions {
+            self.decode_pipeline.dispatch(transaction);
         }
 
-        let new_trades: Vec<Trade> = try_join_all(futures).await?.into_iter().flatten().collect();
+        let mut new_trades = self.decode_pipeline.drain_trades();
+        new_trades.retain(|trade| self.meets_reserve_threshold(trade));
+        new_trades.extend(direct_swap_trades);
 
         for trade in new_trades {
             self.trades.insert(trade.protocol, trade);
@@ -238,34 +861,82 @@ impl<'a> TxPool<'a> {
         new_transactions
     }
 
-    fn filter_router_transactions(
-        &self,
-        transactions: Vec<Transaction>,
-    ) -> Vec<FilteredTransactions> {
-        let mut router_addresses = HashMap::new();
-        for protocol in self.protocols.values() {
-            let router_address = protocol.router.address();
-            let filtered = FilteredTransactions::new(protocol);
-            router_addresses.insert(router_address, filtered);
+    /// The reserve-share half of the configured trade-size filters, applied
+    /// once a trade comes back from its decode worker since it needs this
+    /// `TxPool`'s live reserves rather than anything a worker can see on its
+    /// own. See `below_min_trade_size` for the other half.
+    fn meets_reserve_threshold(&self, trade: &Trade) -> bool {
+        let Some(bps) = *MIN_VICTIM_TRADE_RESERVE_BPS else {
+            return true;
+        };
+        let Some(first_hop) = trade.path.pair_order.first() else {
+            return true;
+        };
+        let Some(pair) = lookup_pair(
+            trade.protocol,
+            first_hop.pair_addresses,
+            &self.protocols,
+            &self.custom_pairs,
+        ) else {
+            return true;
+        };
+
+        let pool_reserves = U256::from(pair.reserve0).saturating_add(U256::from(pair.reserve1));
+        if pool_reserves.is_zero() {
+            return true;
+        }
+        let threshold = pool_reserves.saturating_mul(U256::from(bps)) / U256::from(10_000);
+        if trade.params.input_amount_bound() >= threshold {
+            return true;
         }
 
-        for transaction in transactions {
-            if let Some(to) = transaction.to {
-                if let Some(filtered) = router_addresses.get_mut(&to) {
-                    {
-                        filtered.transactions.push(transaction);
-                    }
-                }
-            }
+        if let Some(stats) = self
+            .protocols
+            .get(&trade.protocol)
+            .and_then(|protocol| self.decoding_stats.get(&protocol.router.address()))
+        {
+            stats.record_dropped(DropReason::TooSmall);
         }
-        router_addresses.into_values().collect()
+        false
     }
 
-    fn simulate_trades(&mut self, input_amount: U256) -> Vec<PossibleArbitrage> {
-        let mut possible_arbitrages = Vec::new();
-        let amounts = (1..=10).map(|num| (input_amount / U256::from(10)) * num);
+    /// Runs one search pass per strategy. `block_subscription` is checked
+    /// once per trade — not inside `find_best_trade_for_target`'s own
+    /// DFS, which isn't instrumented with yield points — so a new block can
+    /// cut the pass short between trades rather than only after it
+    /// finishes entirely. On a cut-short pass, everything found so far is
+    /// returned with `stale_risk` set (a later, unexamined trade might have
+    /// beaten it) alongside the block that interrupted the search, so the
+    /// caller can still process that block instead of losing it.
+    fn simulate_trades(
+        &mut self,
+        strategies: &[Strategy],
+        inputs: &[U256],
+        block_subscription: &mut SubscriptionStream<'_, Ws, Block<H256>>,
+    ) -> (Vec<Vec<PossibleArbitrage>>, Option<Block<H256>>) {
+        // Keyed by token path, keeping only the most profitable candidate
+        // per unique opportunity: several victim trades or input amounts
+        // can easily surface the same path. One map per strategy, since
+        // the same path can be the best opportunity for more than one of
+        // them.
+        let mut best_by_path: Vec<FxHashMap<Vec<Address>, PossibleArbitrage>> =
+            strategies.iter().map(|_| FxHashMap::default()).collect();
 
         for (address, mut trade) in self.trades.iter_mut() {
+            if let Some(block) = block_subscription.next().now_or_never() {
+                let block = block.expect("No block?");
+                for candidates in &mut best_by_path {
+                    for arbitrage in candidates.values_mut() {
+                        arbitrage.mark_stale_risk();
+                    }
+                }
+                let results = best_by_path
+                    .into_iter()
+                    .map(|map| map.into_values().collect())
+                    .collect();
+                return (results, Some(block));
+            }
+
             if trade.simulated {
                 continue;
             }
@@ -279,12 +950,92 @@ impl<'a> TxPool<'a> {
                 .protocols
                 .get_mut(address)
                 .expect("Protocol not found in protocols");
-            let changed = trade.simulate(mut_protocol, checked_amounts);
+            let changed = match trade.simulate(mut_protocol, checked_amounts) {
+                Ok(changed) => changed,
+                Err(_) => continue,
+            };
 
-            for amount in amounts.clone() {
-                let (path, output) =
-                    find_best_trade(&mut self.protocols, amount, &self.custom_pairs);
-                possible_arbitrages.push(PossibleArbitrage::new(path, trade.gas, output, amount));
+            for (strategy_index, strategy) in strategies.iter().enumerate() {
+                let input_amount = inputs[strategy_index];
+                let amounts = (1..=10).map(|num| (input_amount / U256::from(10)) * num);
+                for amount in amounts {
+                    let (path, output) = match find_best_trade_for_target(
+                        &self.protocols,
+                        amount,
+                        &self.custom_pairs,
+                        strategy.target,
+                        &self.pair_blacklist,
+                        strategy.max_hops,
+                        strategy.token_universe.as_ref(),
+                        strategy.profit_tokens.as_ref(),
+                        *NATIVE_TOKEN_ADDRESS,
+                        &mut self.token_index,
+                        strategy.base_tokens.as_ref(),
+                    ) {
+                        Ok(result) => result,
+                        Err(_) => continue,
+                    };
+                    if self.is_cooling_down(&path)
+                        || path_conflicts_with_in_flight(
+                            &path,
+                            &self.in_flight_pools,
+                            &self.protocols,
+                            &self.custom_pairs,
+                        )
+                    {
+                        continue;
+                    }
+                    let settled_in_target = path.token_order.last() == Some(&strategy.target);
+                    let arbitrage = if settled_in_target {
+                        PossibleArbitrage::new(
+                            path,
+                            trade.gas,
+                            output,
+                            amount,
+                            trade.tx_hash,
+                            *address,
+                            self.chain_profile,
+                        )
+                    } else {
+                        let settlement_token = *path.token_order.last().unwrap();
+                        let output_in_native = value_in_native(
+                            settlement_token,
+                            output,
+                            *NATIVE_TOKEN_ADDRESS,
+                            &self.protocols,
+                            &self.custom_pairs,
+                            strategy.max_hops,
+                            &mut self.token_index,
+                        );
+                        let input_in_native = value_in_native(
+                            strategy.target,
+                            amount,
+                            *NATIVE_TOKEN_ADDRESS,
+                            &self.protocols,
+                            &self.custom_pairs,
+                            strategy.max_hops,
+                            &mut self.token_index,
+                        );
+                        let profit = output_in_native.saturating_sub(input_in_native);
+                        PossibleArbitrage::with_profit(
+                            path,
+                            trade.gas,
+                            output,
+                            amount,
+                            profit,
+                            trade.tx_hash,
+                            *address,
+                            self.chain_profile,
+                        )
+                    };
+                    match best_by_path[strategy_index].get(&arbitrage.path.token_order) {
+                        Some(existing) if existing.profit >= arbitrage.profit => {}
+                        _ => {
+                            best_by_path[strategy_index]
+                                .insert(arbitrage.path.token_order.clone(), arbitrage);
+                        }
+                    }
+                }
             }
 
             let protocol = self
@@ -294,7 +1045,11 @@ impl<'a> TxPool<'a> {
             protocol.unsimualte_trade(changed);
             trade.simulated = true
         }
-        possible_arbitrages
+        let results = best_by_path
+            .into_iter()
+            .map(|map| map.into_values().collect())
+            .collect();
+        (results, None)
     }
 
     pub fn mark_unsimulated(&mut self) {
@@ -303,35 +1058,42 @@ impl<'a> TxPool<'a> {
         }
     }
 
-    pub async fn remove_done_trades(&mut self, hashes: Vec<H256>) -> Result<()> {
-        self.trades
-            .retain(|_address, tx| !hashes.contains(&tx.tx_hash));
-        let mut handles = Vec::new();
-        for trade in self.trades.values() {
-            let client_copy = self.client.clone();
-            let hash = trade.tx_hash;
-            handles.push(tokio::spawn(async move {
-                (hash, client_copy.get_transaction(hash).await)
-            }))
-        }
-
-        let outcome = join_all(handles).await;
-        let mut hashes_to_remove = Vec::new();
-        for item in outcome {
-            let (input_hash, output) = item?;
-            match output? {
-                None => hashes_to_remove.push(input_hash),
-                Some(tx) => {
-                    if tx.block_number.is_some() {
-                        hashes_to_remove.push(input_hash)
-                    }
-                }
-            }
+    fn evict_stale_trades(&mut self) {
+        let before = self.trades.len();
+        self.trades.retain(|_address, tx| tx.age() < MAX_TRADE_AGE);
+        let evicted = before - self.trades.len();
+        if evicted > 0 {
+            println!("Evicted {} stale pending trade(s)", evicted);
         }
+    }
 
+    /// Drops trades that the latest block's contents show are settled:
+    /// either mined in this block, or simply stale per `evict_stale_trades`.
+    /// Previously this diffed against one block and then re-confirmed every
+    /// remaining trade with its own `get_transaction` call; diffing the
+    /// block's transaction hashes directly is the same information without
+    /// the extra per-trade round trip.
+    pub async fn remove_done_trades(&mut self, hashes: Vec<H256>) -> Result<()> {
+        self.evict_stale_trades();
+        let hashes: FxHashSet<H256> = hashes.into_iter().collect();
         self.trades
-            .retain(|_address, tx| !hashes_to_remove.contains(&tx.tx_hash));
+            .retain(|_address, tx| !hashes.contains(&tx.tx_hash));
 
         Ok(())
     }
 }
+
+fn path_conflicts_with_in_flight(
+    path: &Path,
+    in_flight_pools: &FxHashSet<Address>,
+    protocols: &HashMap<Address, Protocol>,
+    custom_pairs: &FxHashMap<(Address, Address), Pair>,
+) -> bool {
+    if in_flight_pools.is_empty() {
+        return false;
+    }
+    match path.pool_addresses(protocols, custom_pairs) {
+        Ok(pools) => pools.iter().any(|pool| in_flight_pools.contains(pool)),
+        Err(_) => false,
+    }
+}
@@ -1,23 +1,80 @@
 use std::collections::hash_map::Values;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
-use std::iter::{zip, FlatMap};
+use std::iter::zip;
+use std::str::FromStr;
 use std::sync::Arc;
 
+use std::env;
+
 use anyhow::{anyhow, ensure, Result};
 use deadpool_sqlite::rusqlite::{params, params_from_iter};
 use deadpool_sqlite::Pool;
 use ethers::abi::{Abi, Token};
 use ethers::prelude::*;
-use futures::future::try_join_all;
+use futures::future::{join_all, try_join_all};
+use lazy_static::lazy_static;
+use rand::seq::IteratorRandom;
+use rand::thread_rng;
 
 use crate::pair::{Pair, PairAddress, PartialPair};
 use crate::txpool::TxPool;
+use crate::{ABI_CACHE_DIR, BAD_TOKENS_PATH};
 
 pub type WSClient = Arc<Provider<Ws>>;
 
 abigen!(SwapPool, "abis/pool.json");
-const BAD_TOKENS_PATH: &str = "bad_tokens.json";
+
+lazy_static! {
+    static ref EXPLORER_API_URL: String = env::var("EXPLORER_API_URL").unwrap();
+    static ref EXPLORER_API_KEY: String = env::var("EXPLORER_API_KEY").unwrap();
+}
+
+#[derive(serde::Deserialize)]
+struct ExplorerAbiResponse {
+    status: String,
+    message: String,
+    result: String,
+}
+
+/// Fetches a verified contract's ABI from an Etherscan-compatible block
+/// explorer API, for protocols onboarded without a hand-collected ABI file.
+async fn fetch_abi_from_explorer(address: Address) -> Result<String> {
+    let url = format!(
+        "{}?module=contract&action=getabi&address={:#x}&apikey={}",
+        EXPLORER_API_URL.as_str(),
+        address,
+        EXPLORER_API_KEY.as_str()
+    );
+    let response: ExplorerAbiResponse = reqwest::get(url).await?.json().await?;
+    ensure!(
+        response.status == "1",
+        "Explorer could not return ABI for {:#x}: {}",
+        address,
+        response.message
+    );
+    Ok(response.result)
+}
+
+/// Loads a contract ABI from the configured path, or fetches and caches it
+/// from a block explorer when the protocol entry does not specify one.
+pub(crate) async fn resolve_abi(path: Option<String>, address: Address) -> Result<Abi> {
+    let raw_json = match path {
+        Some(path) => tokio::fs::read_to_string(path).await?,
+        None => {
+            let cache_path = format!("{}/{:#x}.json", ABI_CACHE_DIR.as_str(), address);
+            match tokio::fs::read_to_string(&cache_path).await {
+                Ok(cached) => cached,
+                Err(_) => {
+                    let fetched = fetch_abi_from_explorer(address).await?;
+                    tokio::fs::write(&cache_path, &fetched).await?;
+                    fetched
+                }
+            }
+        }
+    };
+    Ok(serde_json::from_str(raw_json.as_str())?)
+}
 
 struct GetPairCall<'a> {
     protocol: &'a Protocol,
@@ -78,24 +135,166 @@ impl DbAddition {
     }
 }
 
-#[derive(serde::Deserialize)]
-struct RawProtocol {
+pub(crate) struct RawProtocol {
     factory_addr: Address,
-    factory_abi: String,
+    factory_abi: Option<String>,
     swap_fee: u32,
     name: String,
     router_address: Address,
-    router_abi: String,
+    router_abi: Option<String>,
+    router_aliases: Vec<Address>,
+}
+
+impl RawProtocol {
+    pub(crate) const fn new(
+        factory_addr: Address,
+        swap_fee: u32,
+        name: String,
+        router_address: Address,
+    ) -> Self {
+        Self {
+            factory_addr,
+            factory_abi: None,
+            swap_fee,
+            name,
+            router_address,
+            router_abi: None,
+            router_aliases: Vec::new(),
+        }
+    }
+}
+
+/// Shared fallbacks for `protocols.json`'s `protocols` entries, so a
+/// deployment with dozens of forks on the same factory/router ABI and fee
+/// tier doesn't have to repeat them on every entry. Any field an entry sets
+/// for itself takes precedence over the matching default.
+#[derive(Default, serde::Deserialize)]
+struct ProtocolDefaults {
+    #[serde(default)]
+    factory_abi: Option<String>,
+    #[serde(default)]
+    router_abi: Option<String>,
+    #[serde(default)]
+    swap_fee: Option<u32>,
+}
+
+/// One `protocols.json` entry before `ProtocolDefaults` has been folded in.
+/// `factory_addr`/`router_address` are deserialized as strings rather than
+/// `Address` directly so `${VAR}` references can be interpolated against
+/// the process environment before parsing, e.g. a deployment-specific
+/// factory address injected via `${MYCHAIN_FACTORY}` instead of being
+/// hardcoded per environment.
+#[derive(serde::Deserialize)]
+struct RawProtocolEntry {
+    factory_addr: String,
+    #[serde(default)]
+    factory_abi: Option<String>,
+    #[serde(default)]
+    swap_fee: Option<u32>,
+    name: String,
+    router_address: String,
+    #[serde(default)]
+    router_abi: Option<String>,
+    /// Additional router addresses (e.g. a legacy router deployment still
+    /// carrying traffic, or a separate fee-on-transfer-supporting router)
+    /// whose calldata should be decoded as if it were sent to
+    /// `router_address`: same ABI, same factory, same pairs. Absent by
+    /// default: only `router_address`'s own traffic is watched, as before.
+    #[serde(default)]
+    router_aliases: Vec<String>,
+}
+
+impl RawProtocolEntry {
+    /// Folds in `defaults` for any field this entry left unset, interpolates
+    /// `${VAR}` references in the address fields, and parses them, returning
+    /// an error that names the offending protocol and field rather than a
+    /// bare serde parse failure.
+    fn resolve(self, defaults: &ProtocolDefaults) -> Result<RawProtocol> {
+        let swap_fee = self.swap_fee.or(defaults.swap_fee).ok_or_else(|| {
+            anyhow!(
+                "protocol {:?} has no swap_fee, and protocols.json sets no default swap_fee",
+                self.name
+            )
+        })?;
+        let factory_addr = resolve_address(&self.factory_addr)
+            .map_err(|error| anyhow!("protocol {:?} factory_addr: {}", self.name, error))?;
+        let router_address = resolve_address(&self.router_address)
+            .map_err(|error| anyhow!("protocol {:?} router_address: {}", self.name, error))?;
+        let router_aliases = self
+            .router_aliases
+            .iter()
+            .map(|alias| resolve_address(alias))
+            .collect::<Result<Vec<Address>>>()
+            .map_err(|error| anyhow!("protocol {:?} router_aliases: {}", self.name, error))?;
+        Ok(RawProtocol {
+            factory_addr,
+            factory_abi: self.factory_abi.or_else(|| defaults.factory_abi.clone()),
+            swap_fee,
+            name: self.name,
+            router_address,
+            router_abi: self.router_abi.or_else(|| defaults.router_abi.clone()),
+            router_aliases,
+        })
+    }
+}
+
+/// Top-level shape of `protocols.json`: a `defaults` block (optional, falls
+/// back to empty) and the list of protocol entries that draw on it.
+#[derive(serde::Deserialize)]
+struct ProtocolsFile {
+    #[serde(default)]
+    defaults: ProtocolDefaults,
+    protocols: Vec<RawProtocolEntry>,
+}
+
+/// Substitutes `${VAR}` references in `raw` with the matching environment
+/// variable, then parses the result as an `Address`. Used for
+/// `factory_addr`/`router_address`, which otherwise have to be hardcoded
+/// per deployment.
+fn resolve_address(raw: &str) -> Result<Address> {
+    let mut resolved = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            return Err(anyhow!("unterminated ${{...}} in {:?}", raw));
+        };
+        let end = start + end;
+        resolved.push_str(&rest[..start]);
+        let var_name = &rest[start + 2..end];
+        let value = env::var(var_name).map_err(|_| {
+            anyhow!(
+                "environment variable {:?} referenced in {:?} is not set",
+                var_name,
+                raw
+            )
+        })?;
+        resolved.push_str(&value);
+        rest = &rest[end + 1..];
+    }
+    resolved.push_str(rest);
+    Address::from_str(resolved.trim())
+        .map_err(|error| anyhow!("invalid address {:?}: {}", resolved, error))
 }
 
 #[derive(Debug)]
 pub struct Protocol {
     pub factory: ethers::contract::Contract<WSClient>,
     pub router: ethers::contract::Contract<WSClient>,
+    /// Extra router addresses (old deployments, fee-on-transfer-supporting
+    /// variants, ...) whose calldata is decoded with `router`'s ABI as if
+    /// it were sent to `router` itself, since they all move the same pairs.
+    pub router_aliases: Vec<Address>,
     swap_fee: u32,
     name: String,
     pub pairs: HashMap<(Address, Address), Pair>,
     pool: Arc<Pool>,
+    /// Set by `audit_cross_protocol_quotes` when this protocol's pairs
+    /// persistently quote outlier prices against the same token pairs on
+    /// other protocols - a sign of broken fee config or exotic fork math
+    /// rather than a real arbitrage. Suspended protocols stay loaded (so
+    /// they can be un-suspended by a restart once fixed) but `get_all_pairs`
+    /// excludes their pairs from routing.
+    pub suspended: bool,
 }
 
 impl Hash for Protocol {
@@ -106,11 +305,9 @@ impl Hash for Protocol {
 
 // Private Functions
 impl Protocol {
-    async fn new(raw: RawProtocol, client: WSClient, pool: Arc<Pool>) -> Result<Self> {
-        let factory_abi: Abi =
-            serde_json::from_str(tokio::fs::read_to_string(raw.factory_abi).await?.as_str())?;
-        let router_abi: Abi =
-            serde_json::from_str(tokio::fs::read_to_string(raw.router_abi).await?.as_str())?;
+    pub(crate) async fn new(raw: RawProtocol, client: WSClient, pool: Arc<Pool>) -> Result<Self> {
+        let factory_abi = resolve_abi(raw.factory_abi, raw.factory_addr).await?;
+        let router_abi = resolve_abi(raw.router_abi, raw.router_address).await?;
         let factory =
             ethers::contract::Contract::new(raw.factory_addr, factory_abi, client.clone());
         let router =
@@ -118,13 +315,21 @@ impl Protocol {
         Ok(Self {
             factory,
             router,
+            router_aliases: raw.router_aliases,
             swap_fee: raw.swap_fee,
             name: raw.name,
             pairs: HashMap::new(),
             pool,
+            suspended: false,
         })
     }
 
+    /// Every router address this protocol's traffic should be recognized
+    /// under: its primary `router` plus any `router_aliases`.
+    pub fn router_addresses(&self) -> impl Iterator<Item = Address> + '_ {
+        std::iter::once(self.router.address()).chain(self.router_aliases.iter().copied())
+    }
+
     async fn update_excluded_pairs_for_protocol(&self, bad_tokens_file: &str) -> Result<()> {
         let name = self.name.clone();
         let bad_tokens: Vec<String> =
@@ -261,9 +466,101 @@ impl Protocol {
         }).await.map_err(|oops| anyhow!(oops.to_string()))?
     }
 
+    /// Multicalls `token0()/token1()` on every pair loaded from the DB and
+    /// compares them against the stored values, flagging any mismatch
+    /// (selfdestructed/upgraded pair) as excluded instead of letting a
+    /// corrupt row silently poison quotes.
+    async fn validate_db_pairs(
+        &self,
+        partials: Vec<PartialPair>,
+        client: WSClient,
+    ) -> Result<Vec<PartialPair>> {
+        if partials.is_empty() {
+            return Ok(partials);
+        }
+
+        let mut multicall: Multicall<WSClient> =
+            Multicall::new(self.factory.client().clone(), None)
+                .await?
+                .version(MulticallVersion::Multicall);
+
+        for partial in &partials {
+            let contract = partial.address.generate_pool_contract(client.clone());
+            multicall.add_call(contract.method::<_, Address>("token0", ())?, false);
+            multicall.add_call(contract.method::<_, Address>("token1", ())?, false);
+        }
+
+        let tokens = multicall.call_raw().await?;
+        let chunks = tokens.chunks(2);
+        ensure!(
+            chunks.len() == partials.len(),
+            "Differing lengths of pairs and multicall returns"
+        );
+
+        let mut valid = Vec::with_capacity(partials.len());
+        let mut stale_addresses = Vec::new();
+        for (partial, chunk) in zip(partials, chunks) {
+            let matches = chunk[0].clone().into_address() == Some(partial.token0)
+                && chunk[1].clone().into_address() == Some(partial.token1);
+            if matches {
+                valid.push(partial);
+            } else {
+                stale_addresses.push(partial.address.0);
+            }
+        }
+
+        if !stale_addresses.is_empty() {
+            self.exclude_stale_pairs(&stale_addresses).await?;
+        }
+
+        Ok(valid)
+    }
+
+    async fn exclude_stale_pairs(&self, addresses: &[Address]) -> Result<()> {
+        let conn = self.pool.get().await?;
+        let addresses: Vec<String> = addresses
+            .iter()
+            .map(|address| format!("{:#x}", address))
+            .collect();
+        let qmarks = repeat_vars(addresses.len());
+        let sql = format!(
+            "UPDATE pairs SET excluded = TRUE WHERE address IN ({})",
+            qmarks
+        );
+
+        let stale_count = addresses.len();
+        conn.interact(move |conn| conn.execute(sql.as_str(), params_from_iter(addresses)))
+            .await
+            .map_err(|oops| anyhow!(oops.to_string()))??;
+
+        println!(
+            "Flagged {} pairs as excluded after on-chain mismatch",
+            stale_count
+        );
+
+        Ok(())
+    }
+
     async fn load_db_pairs(&mut self, client: WSClient) -> Result<()> {
         let partials = self.get_pair_addresses_from_db().await?;
-        for partial in partials {
+        let partials = self.validate_db_pairs(partials, client.clone()).await?;
+
+        let factory_address = self.factory.address();
+        let decimal_futures = partials.iter().map(|partial| {
+            let client = client.clone();
+            async move {
+                let decimals0 = crate::RPC_BUDGET
+                    .run(factory_address, fetch_decimals(partial.token0, client.clone()))
+                    .await;
+                let decimals1 = crate::RPC_BUDGET
+                    .run(factory_address, fetch_decimals(partial.token1, client))
+                    .await;
+                (decimals0, decimals1)
+            }
+        });
+        let decimals = join_all(decimal_futures).await;
+
+        for (partial, (decimals0, decimals1)) in zip(partials, decimals) {
             let address = partial.address;
             let contract = address.generate_pool_contract(client.clone());
             self.pairs.insert(
@@ -274,6 +571,8 @@ impl Protocol {
                     partial.token1,
                     self.swap_fee,
                     self.factory.address(),
+                    decimals0,
+                    decimals1,
                 ),
             );
         }
@@ -300,11 +599,16 @@ impl Protocol {
         Ok(protocol)
     }
 
-    async fn get_reserves(mut protocol: Self, address: Address) -> Result<(Self, Address)> {
+    async fn get_reserves(
+        mut protocol: Self,
+        address: Address,
+        block_number: U64,
+    ) -> Result<(Self, Address)> {
         let mut multicall: Multicall<WSClient> =
             Multicall::new(protocol.factory.client().clone(), None)
                 .await?
-                .version(MulticallVersion::Multicall);
+                .version(MulticallVersion::Multicall)
+                .block(block_number);
 
         for pair in protocol.pairs.values_mut() {
             multicall.add_call(
@@ -357,8 +661,14 @@ pub async fn generate_protocols(
     file_path: &str,
     pool: Arc<Pool>,
 ) -> Result<HashMap<Address, Protocol>> {
-    let raw_protocols: Vec<RawProtocol> =
-        serde_json::from_str(tokio::fs::read_to_string(file_path).await?.as_str())?;
+    let protocols_file: ProtocolsFile =
+        serde_json::from_str(tokio::fs::read_to_string(file_path).await?.as_str())
+            .map_err(|error| anyhow!("Failed to parse {}: {}", file_path, error))?;
+    let raw_protocols = protocols_file
+        .protocols
+        .into_iter()
+        .map(|entry| entry.resolve(&protocols_file.defaults))
+        .collect::<Result<Vec<RawProtocol>>>()?;
     let mut tasks = Vec::with_capacity(raw_protocols.len());
     for raw in raw_protocols {
         tasks.push(tokio::spawn(Protocol::new(
@@ -389,7 +699,7 @@ pub async fn update_all_pairs(
         handles.push(tokio::spawn(Protocol::update_pairs(
             protocol,
             client.clone(),
-            BAD_TOKENS_PATH,
+            BAD_TOKENS_PATH.as_str(),
         )));
     }
 
@@ -404,21 +714,241 @@ pub async fn update_all_pairs(
 
 impl<'a> TxPool<'a> {
     pub async fn get_all_reserves(&mut self) -> Result<()> {
-        let protocols = &mut self.protocols;
-        let mut handles = Vec::with_capacity(protocols.len());
-        for (address, protocol) in protocols.drain() {
-            handles.push(tokio::spawn(Protocol::get_reserves(protocol, address)));
+        refresh_all_reserves(&mut self.protocols).await
+    }
+}
+
+/// Refetches on-chain reserves for every pair across `protocols`, shared by
+/// `TxPool::get_all_reserves` and any other consumer that holds its own copy
+/// of the protocol map (e.g. the standalone quoting service).
+///
+/// Every protocol's multicall batch is pinned to the same block number,
+/// fetched once up front, so a refresh can never mix reserves read a block
+/// apart across protocols (or across a protocol's own concurrently-launched
+/// tasks) — a window that otherwise produces phantom arbitrages during
+/// volatile periods.
+pub async fn refresh_all_reserves(protocols: &mut HashMap<Address, Protocol>) -> Result<()> {
+    let Some(any_protocol) = protocols.values().next() else {
+        return Ok(());
+    };
+    let block_number = any_protocol.factory.client().get_block_number().await?;
+
+    let mut handles = Vec::with_capacity(protocols.len());
+    for (address, protocol) in protocols.drain() {
+        handles.push(tokio::spawn(async move {
+            crate::RPC_BUDGET
+                .run(
+                    address,
+                    Protocol::get_reserves(protocol, address, block_number),
+                )
+                .await
+        }));
+    }
+
+    let outcome = futures::future::try_join_all(handles).await?;
+
+    for item in outcome {
+        let (protocol, address) = item?;
+        protocols.insert(address, protocol);
+    }
+
+    Ok(())
+}
+
+/// Early-warning check for bugs in the incremental reserve updates
+/// (`Trade::simulate`/`unsimualte_trade`, `refresh_all_reserves`): samples up
+/// to `sample_size` pairs per protocol, re-reads their reserves fresh from
+/// chain, and overwrites every sampled pair's locally tracked value with
+/// them, first printing an alert for each pair whose local value had drifted
+/// from the on-chain value by more than `max_drift_bps`. Returns the number
+/// of such drifted pairs.
+pub async fn audit_reserve_divergence(
+    protocols: &mut HashMap<Address, Protocol>,
+    sample_size: usize,
+    max_drift_bps: u32,
+) -> Result<usize> {
+    let mut corrected = 0;
+    let Some(any_protocol) = protocols.values().next() else {
+        return Ok(0);
+    };
+    let block_number = any_protocol.factory.client().get_block_number().await?;
+
+    for protocol in protocols.values_mut() {
+        let sample: Vec<(Address, Address)> = protocol
+            .pairs
+            .keys()
+            .copied()
+            .choose_multiple(&mut thread_rng(), sample_size);
+        if sample.is_empty() {
+            continue;
+        }
+
+        let mut multicall: Multicall<WSClient> =
+            Multicall::new(protocol.factory.client().clone(), None)
+                .await?
+                .version(MulticallVersion::Multicall)
+                .block(block_number);
+        for key in &sample {
+            let pair = protocol
+                .pairs
+                .get(key)
+                .expect("Sampled key missing from pairs");
+            multicall.add_call(
+                pair.contract
+                    .method::<_, (u128, u128, u32)>("getReserves", ())?,
+                false,
+            );
+        }
+
+        let tokens = multicall.call_raw().await?;
+        ensure!(
+            sample.len() == tokens.len(),
+            "Differing lengths of sample and multicall returns"
+        );
+
+        for (key, token) in zip(&sample, tokens) {
+            let mut reserves = token
+                .into_tuple()
+                .ok_or_else(|| anyhow!("Token cannot convert into tuple"))?;
+            let fresh0 = reserves
+                .swap_remove(0)
+                .into_uint()
+                .ok_or_else(|| anyhow!("Token cannot convert into uint"))?
+                .as_u128();
+            let fresh1 = reserves
+                .swap_remove(1)
+                .into_uint()
+                .ok_or_else(|| anyhow!("Token cannot convert into uint"))?
+                .as_u128();
+
+            let pair = protocol
+                .pairs
+                .get_mut(key)
+                .expect("Sampled key missing from pairs");
+            let drift = reserve_drift_bps(pair.reserve0, fresh0).max(reserve_drift_bps(pair.reserve1, fresh1));
+            if drift > max_drift_bps {
+                println!(
+                    "Reserve drift of {} bps detected for pair {:?}: local ({}, {}) vs on-chain ({}, {}), correcting",
+                    drift, key, pair.reserve0, pair.reserve1, fresh0, fresh1
+                );
+                corrected += 1;
+            }
+            pair.reserve0 = fresh0;
+            pair.reserve1 = fresh1;
         }
+    }
 
-        let outcome = futures::future::try_join_all(handles).await?;
+    Ok(corrected)
+}
 
-        for item in outcome {
-            let (protocol, address) = item?;
-            self.protocols.insert(address, protocol);
+/// Drift between a locally tracked reserve and a fresh on-chain read,
+/// expressed in basis points of the on-chain value.
+fn reserve_drift_bps(local: u128, fresh: u128) -> u32 {
+    if fresh == 0 {
+        return if local == 0 { 0 } else { u32::MAX };
+    }
+    let diff = local.abs_diff(fresh);
+    diff.saturating_mul(10_000)
+        .checked_div(fresh)
+        .and_then(|value| u32::try_from(value).ok())
+        .unwrap_or(u32::MAX)
+}
+
+/// Minimum number of protocols quoting a pair before a divergent price is
+/// trusted as evidence against the protocol that's off, rather than just two
+/// protocols disagreeing with no way to tell which one is wrong.
+const MIN_QUOTE_COMPARISONS: usize = 3;
+
+/// Compares every protocol's price for each token pair it shares with other
+/// protocols against the group's median, and suspends any protocol whose
+/// shared pairs mostly disagree with their peers by more than
+/// `max_divergence_bps` - the signature of a broken fee config or exotic
+/// fork math rather than a genuine arbitrage opportunity. Suspension only
+/// ever turns on here; clearing it back up requires a restart once the
+/// underlying protocol is fixed. Returns the names of protocols newly
+/// suspended by this pass so the caller can alert on them.
+pub fn audit_cross_protocol_quotes(
+    protocols: &mut HashMap<Address, Protocol>,
+    max_divergence_bps: u32,
+) -> Vec<String> {
+    let mut by_pair: HashMap<(Address, Address), Vec<(Address, U256)>> = HashMap::new();
+    for (factory, protocol) in protocols.iter() {
+        if protocol.suspended {
+            continue;
         }
+        for pair in protocol.pairs.values() {
+            let (token0, token1) = pair.get_tokens();
+            let (key, reserve0, reserve1) = if token0 < token1 {
+                ((token0, token1), pair.reserve0, pair.reserve1)
+            } else {
+                ((token1, token0), pair.reserve1, pair.reserve0)
+            };
+            if reserve0 == 0 {
+                continue;
+            }
+            let price = U256::from(reserve1) * U256::from(10).pow(18.into()) / U256::from(reserve0);
+            by_pair.entry(key).or_default().push((*factory, price));
+        }
+    }
 
-        Ok(())
+    let mut compared: HashMap<Address, usize> = HashMap::new();
+    let mut diverged: HashMap<Address, usize> = HashMap::new();
+    for quotes in by_pair.values() {
+        if quotes.len() < MIN_QUOTE_COMPARISONS {
+            continue;
+        }
+        let prices: Vec<U256> = quotes.iter().map(|&(_, price)| price).collect();
+        let median = median_price(&prices);
+        for &(factory, price) in quotes {
+            *compared.entry(factory).or_insert(0) += 1;
+            if price_divergence_bps(price, median) > max_divergence_bps {
+                *diverged.entry(factory).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut newly_suspended = Vec::new();
+    for (factory, diverged_count) in diverged {
+        let total = compared.get(&factory).copied().unwrap_or(0);
+        if total >= MIN_QUOTE_COMPARISONS && diverged_count * 2 > total {
+            if let Some(protocol) = protocols.get_mut(&factory) {
+                protocol.suspended = true;
+                newly_suspended.push(protocol.name.clone());
+            }
+        }
     }
+
+    newly_suspended
+}
+
+fn median_price(prices: &[U256]) -> U256 {
+    let mut sorted = prices.to_vec();
+    sorted.sort();
+    sorted[sorted.len() / 2]
+}
+
+/// Divergence of `price` from `median`, expressed in basis points of the
+/// median.
+fn price_divergence_bps(price: U256, median: U256) -> u32 {
+    if median.is_zero() {
+        return if price.is_zero() { 0 } else { u32::MAX };
+    }
+    let diff = if price > median {
+        price - median
+    } else {
+        median - price
+    };
+    diff.saturating_mul(U256::from(10_000))
+        .checked_div(median)
+        .and_then(|value| u32::try_from(value).ok())
+        .unwrap_or(u32::MAX)
+}
+
+/// Looks up an ERC-20's `decimals()`, falling back to the common 18 when the
+/// call fails (e.g. non-standard tokens that omit it).
+async fn fetch_decimals(token: Address, client: WSClient) -> u8 {
+    let contract = crate::erc20::new(token, client);
+    contract.decimals().call().await.unwrap_or(18)
 }
 
 fn repeat_vars(count: usize) -> String {
@@ -429,12 +959,8 @@ fn repeat_vars(count: usize) -> String {
     s
 }
 
-pub fn get_all_pairs<'a>(
-    protocols: Values<'a, H160, Protocol>,
-) -> FlatMap<
-    Values<'a, Address, Protocol>,
-    Values<'_, (Address, Address), Pair>,
-    fn(&'a Protocol) -> Values<'_, (Address, Address), Pair>,
-> {
-    protocols.flat_map(|item| item.pairs.values())
+pub fn get_all_pairs<'a>(protocols: Values<'a, H160, Protocol>) -> impl Iterator<Item = &'a Pair> {
+    protocols
+        .filter(|protocol| !protocol.suspended)
+        .flat_map(|item| item.pairs.values())
 }
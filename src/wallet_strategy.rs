@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use ethers::prelude::Address;
+use ethers::types::U256;
+
+use crate::chain_profile::ChainProfile;
+use crate::trade::Gas;
+
+#[derive(serde::Deserialize)]
+struct RawWalletStrategy {
+    address: Address,
+    /// Basis points applied to the gas price this wallet submits with,
+    /// relative to the trade's own gas (10000 = unchanged). Lets a subset
+    /// of accounts bid more aggressively so a single trade can be
+    /// submitted across a spread of gas levels instead of one flat price.
+    gas_multiplier_bps: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WalletStrategy {
+    pub gas_multiplier_bps: u32,
+}
+
+impl Default for WalletStrategy {
+    fn default() -> Self {
+        Self {
+            gas_multiplier_bps: 10000,
+        }
+    }
+}
+
+/// Loads per-wallet submission strategies from `file_path`. Wallets with no
+/// entry get the default (unscaled) strategy; a missing file is treated as
+/// "no overrides" rather than an error, since this is an optional knob.
+pub async fn load_wallet_strategies(file_path: &str) -> Result<HashMap<Address, WalletStrategy>> {
+    let raw_json = match tokio::fs::read_to_string(file_path).await {
+        Ok(contents) => contents,
+        Err(_) => return Ok(HashMap::new()),
+    };
+    let raw: Vec<RawWalletStrategy> = serde_json::from_str(raw_json.as_str())?;
+    Ok(raw
+        .into_iter()
+        .map(|entry| {
+            (
+                entry.address,
+                WalletStrategy {
+                    gas_multiplier_bps: entry.gas_multiplier_bps,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Applies a wallet's gas multiplier to the gas the trade would otherwise
+/// use, keeping the two gas price legs of an EIP-1559 transaction in sync,
+/// and downgrades the result to a legacy transaction on chains that don't
+/// accept type-2 transactions. This is ordinary first-submission pricing, not
+/// a replacement bid, so `profile.min_replacement_bump_bps` plays no part
+/// here - that floor only makes sense once something actually resubmits a
+/// stuck nonce at a bumped price.
+pub fn scale_gas(gas: Gas, strategy: WalletStrategy, profile: ChainProfile) -> Gas {
+    let bps = U256::from(strategy.gas_multiplier_bps);
+    let base = U256::from(10000);
+    let scaled = match gas {
+        Gas::Legacy(price) => Gas::Legacy(price.saturating_mul(bps) / base),
+        Gas::London(max_fee, max_priority_fee) => Gas::London(
+            max_fee.saturating_mul(bps) / base,
+            max_priority_fee.saturating_mul(bps) / base,
+        ),
+    };
+    match scaled {
+        Gas::London(max_fee, _) if !profile.supports_eip1559 => Gas::Legacy(max_fee),
+        other => other,
+    }
+}